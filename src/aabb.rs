@@ -49,6 +49,34 @@ impl AABB {
         }
         true
     }
+
+    // Same slab test as `hit`, but returns the parametric interval the ray spends inside the box
+    pub fn intersect(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<(f32, f32)> {
+        let ray_origins = unpack(r.origin());
+        let ray_directions = unpack(r.direction());
+        let min_dims = unpack(&self.minimum);
+        let max_dims = unpack(&self.maximum);
+
+        let mut tmin = tmin;
+        let mut tmax = tmax;
+        for a in 0..3 {
+            let inv_d = 1.0 / ray_directions[a];
+            let mut t0 = (min_dims[a] - ray_origins[a]) * inv_d;
+            let mut t1 = (max_dims[a] - ray_origins[a]) * inv_d;
+            if inv_d < 0.0 {
+                swap(&mut t0, &mut t1);
+            }
+
+            tmin = t0.max(tmin);
+            tmax = t1.min(tmax);
+
+            if tmax <= tmin {
+                return None;
+            }
+        }
+
+        Some((tmin, tmax))
+    }
 }
 
 // Try to get bounding box that encapsulates both boxes
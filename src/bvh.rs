@@ -73,6 +73,13 @@ impl<'a> BVHNode<'a> {
     }
 }
 
+impl<'a> BVHNode<'a> {
+    // The same object on both sides for a node over a single one
+    pub fn children(&self) -> (&HitModel<'a>, &HitModel<'a>) {
+        (&self.left, &self.right)
+    }
+}
+
 impl Hittable for BVHNode<'_> {
     // Recursively performs sub-dividing of hit models until hit found or not hits found
     fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
@@ -117,3 +124,128 @@ fn box_y_compare<'a>(a: &Arc<HitModel<'a>>, b: &Arc<HitModel<'a>>) -> Ordering {
 fn box_z_compare<'a>(a: &Arc<HitModel<'a>>, b: &Arc<HitModel<'a>>) -> Ordering {
     box_compare(a, b, 2).unwrap()
 }
+
+const LEAF_SIZE: usize = 4;
+
+// Flattened BVH over primitives that live in a single owning container (curve segments,
+// triangles), addressed by index. Unlike `BVHNode` this does not need a `HitModel` per primitive,
+// so the container can hold a single material for all of them.
+#[derive(Clone)]
+pub struct IndexedBVH {
+    nodes: Vec<FlatNode>,
+    indices: Vec<usize>,
+}
+
+#[derive(Copy, Clone)]
+struct FlatNode {
+    bbox: AABB,
+    // Leaf: first index into `indices`. Interior: index of the second child (first is next).
+    offset: usize,
+    count: usize, // zero for interior nodes
+}
+
+impl IndexedBVH {
+    pub fn new(boxes: &[AABB]) -> Self {
+        let mut indices: Vec<usize> = (0..boxes.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * boxes.len());
+
+        if !boxes.is_empty() {
+            build_flat(boxes, &mut indices, 0, boxes.len(), &mut nodes);
+        }
+
+        IndexedBVH { nodes, indices }
+    }
+
+    pub fn bounding_box(&self) -> Option<AABB> {
+        self.nodes.first().map(|node| node.bbox)
+    }
+
+    // Finds the closest primitive hit, with `hit_primitive(index, tmin, tmax)` testing a single
+    // primitive.
+    pub fn hit<'a, F>(
+        &self,
+        r: &Ray,
+        tmin: f32,
+        tmax: f32,
+        mut hit_primitive: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f32, f32) -> Option<HitRecord<'a>>,
+    {
+        let mut closest: Option<HitRecord<'a>> = None;
+        let mut closest_t = tmax;
+
+        // Median splits keep the tree depth logarithmic, so a fixed-size stack suffices
+        let mut stack = [0usize; 64];
+        let mut stack_size = if self.nodes.is_empty() { 0 } else { 1 };
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_index = stack[stack_size];
+            let node = &self.nodes[node_index];
+            if node.bbox.intersect(r, tmin, closest_t).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for &index in &self.indices[node.offset..node.offset + node.count] {
+                    if let Some(rec) = hit_primitive(index, tmin, closest_t) {
+                        closest_t = rec.t;
+                        closest = Some(rec);
+                    }
+                }
+            } else {
+                stack[stack_size] = node.offset;
+                stack[stack_size + 1] = node_index + 1;
+                stack_size += 2;
+            }
+        }
+
+        closest
+    }
+}
+
+// Median split along the widest axis of the primitive centroids
+fn build_flat(
+    boxes: &[AABB],
+    indices: &mut [usize],
+    start: usize,
+    end: usize,
+    nodes: &mut Vec<FlatNode>,
+) -> usize {
+    let bbox = indices[start..end]
+        .iter()
+        .map(|&i| boxes[i])
+        .reduce(surrounding_box)
+        .expect("BVH node without primitives");
+
+    let node_index = nodes.len();
+    nodes.push(FlatNode { bbox, offset: start, count: end - start });
+
+    if end - start <= LEAF_SIZE {
+        return node_index;
+    }
+
+    let centroid = |i: usize| vec3::unpack(&((*boxes[i].min() + *boxes[i].max()) * 0.5));
+    let mut lo = [f32::INFINITY; 3];
+    let mut hi = [-f32::INFINITY; 3];
+    for &i in &indices[start..end] {
+        let c = centroid(i);
+        for a in 0..3 {
+            lo[a] = lo[a].min(c[a]);
+            hi[a] = hi[a].max(c[a]);
+        }
+    }
+
+    let axis = (0..3).fold(0, |best, a| if hi[a] - lo[a] > hi[best] - lo[best] { a } else { best });
+    let mid = start + (end - start) / 2;
+    indices[start..end].select_nth_unstable_by(mid - start, |&a, &b| {
+        centroid(a)[axis].partial_cmp(&centroid(b)[axis]).unwrap_or(Ordering::Equal)
+    });
+
+    build_flat(boxes, indices, start, mid, nodes);
+    let second = build_flat(boxes, indices, mid, end, nodes);
+    nodes[node_index] = FlatNode { bbox, offset: second, count: 0 };
+
+    node_index
+}
@@ -48,3 +48,134 @@ pub fn random_range(min: f32, max: f32) -> Color {
         random_double_range(&mut rng, min, max),
     )
 }
+
+// Linear RGB of a blackbody radiator at `temperature` Kelvin, scaled to the given luminance.
+// Integrates Planck's law against the CIE 1931 matching functions; colors outside the RGB gamut
+// (the deepest reds) are clipped.
+pub fn blackbody(temperature: f32, luminance: f32) -> Color {
+    let (x, y, z) = blackbody_xyz(temperature);
+    let scale = if y > 0.0 { luminance / y } else { 0.0 };
+    let rgb = xyz_to_rgb(x * scale, y * scale, z * scale);
+
+    Vec3::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+}
+
+// Luminance of a blackbody at `temperature` Kelvin relative to one at `reference` Kelvin, from
+// Planck's law over the visible range; rises far faster than the fourth power when dim and red
+pub fn blackbody_relative_luminance(temperature: f32, reference: f32) -> f32 {
+    let (_, y, _) = blackbody_xyz(temperature);
+    let (_, reference_y, _) = blackbody_xyz(reference);
+
+    if reference_y > 0.0 {
+        y / reference_y
+    } else {
+        0.0
+    }
+}
+
+// CIE XYZ of a blackbody's spectral radiance at `temperature` Kelvin, up to a constant factor
+fn blackbody_xyz(temperature: f32) -> (f32, f32, f32) {
+    // Second radiation constant hc/k, in nanometre Kelvin
+    const C2: f32 = 1.4388e7;
+
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+    for step in 0..=80 {
+        let lambda = 380.0 + 5.0 * step as f32;
+        let radiance = 1.0 / (lambda.powi(5) * ((C2 / (lambda * temperature)).exp() - 1.0));
+        let (xbar, ybar, zbar) = cie_matching(lambda);
+
+        x += radiance * xbar;
+        y += radiance * ybar;
+        z += radiance * zbar;
+    }
+
+    (x, y, z)
+}
+
+// Linear RGB of a reflectance spectrum over wavelengths in nanometres, under equal-energy light
+// and white balanced so a flat spectrum keeps its value in every channel
+pub fn reflectance_to_rgb(reflectance: impl Fn(f32) -> f32) -> Color {
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+    let (mut white_x, mut white_y, mut white_z) = (0.0, 0.0, 0.0);
+    for step in 0..=40 {
+        let lambda = 380.0 + 10.0 * step as f32;
+        let value = reflectance(lambda);
+        let (xbar, ybar, zbar) = cie_matching(lambda);
+
+        x += value * xbar;
+        y += value * ybar;
+        z += value * zbar;
+        white_x += xbar;
+        white_y += ybar;
+        white_z += zbar;
+    }
+
+    // Saturated spectra can fall outside the RGB gamut, so keep to valid reflectances
+    let rgb = xyz_to_rgb(x, y, z);
+    let white = xyz_to_rgb(white_x, white_y, white_z);
+    Vec3::new(
+        clamp(rgb.x() / white.x(), 0.0, 1.0),
+        clamp(rgb.y() / white.y(), 0.0, 1.0),
+        clamp(rgb.z() / white.z(), 0.0, 1.0),
+    )
+}
+
+fn xyz_to_rgb(x: f32, y: f32, z: f32) -> Color {
+    Vec3::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+// CIE 1931 2° color matching functions at `lambda` nanometres, from the piecewise Gaussian fit
+// of Wyman, Sloan and Shirley (2013)
+fn cie_matching(lambda: f32) -> (f32, f32, f32) {
+    let g = |mu: f32, below: f32, above: f32| {
+        let t = (lambda - mu) / if lambda < mu { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+
+    (
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_reflectance_keeps_its_value() {
+        let rgb = reflectance_to_rgb(|_| 0.4);
+        for channel in [rgb.x(), rgb.y(), rgb.z()] {
+            assert!((channel - 0.4).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn saturated_reflectance_stays_in_range() {
+        // Narrow bands fall outside the RGB gamut
+        for center in [450.0, 500.0, 520.0, 580.0, 650.0] {
+            let rgb = reflectance_to_rgb(
+                |lambda| if (lambda - center).abs() <= 10.0 { 1.0 } else { 0.0 },
+            );
+            for channel in [rgb.x(), rgb.y(), rgb.z()] {
+                assert!((0.0..=1.0).contains(&channel));
+            }
+        }
+    }
+
+    #[test]
+    fn blackbody_luminance_follows_planck() {
+        assert!((blackbody_relative_luminance(1800.0, 1800.0) - 1.0).abs() < 1e-6);
+
+        // Dim red heat brightens far faster than the total power's fourth power, while at
+        // daylight temperatures most of the extra power is beyond the visible range
+        assert!(blackbody_relative_luminance(2000.0, 1000.0) > 1000.0);
+        let ratio = blackbody_relative_luminance(12000.0, 6000.0);
+        assert!(ratio > 2.0 && ratio < 16.0, "ratio {}", ratio);
+    }
+}
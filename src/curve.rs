@@ -0,0 +1,433 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    aabb::AABB,
+    bvh::IndexedBVH,
+    hittable::{HitRecord, Hittable},
+    material::Surface,
+    onb::ONB,
+    ray::Ray,
+    utility::clamp,
+    vec3::{self, Point3, Vec3},
+};
+
+const MAX_SUBDIVISION_DEPTH: i32 = 10;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CurveBasis {
+    Bezier,  // piecewise cubic, 3n + 1 control points
+    BSpline, // uniform cubic, n + 3 control points
+}
+
+impl CurveBasis {
+    // Whether a curve of this basis can be made from `count` control points
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Self::Bezier => count >= 4 && count % 3 == 1,
+            Self::BSpline => count >= 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CurveMode {
+    Flat,  // ribbon that always faces the incoming ray
+    Round, // ribbon shaded as if it were a cylinder
+}
+
+// Input description of a single curve; the width varies linearly along its full length.
+#[derive(Clone)]
+pub struct Curve {
+    control_points: Vec<Point3>,
+    basis: CurveBasis,
+    mode: CurveMode,
+    width0: f32,
+    width1: f32,
+}
+
+impl Curve {
+    pub fn new(
+        control_points: Vec<Point3>,
+        basis: CurveBasis,
+        mode: CurveMode,
+        width0: f32,
+        width1: f32,
+    ) -> Self {
+        assert!(
+            basis.accepts(control_points.len()),
+            "Wrong number of control points for curve basis"
+        );
+        Curve { control_points, basis, mode, width0, width1 }
+    }
+
+    // Converts the curve into cubic Bézier pieces
+    fn bezier_segments(&self) -> Vec<[Point3; 4]> {
+        let cp = &self.control_points;
+        match self.basis {
+            CurveBasis::Bezier => {
+                cp.windows(4).step_by(3).map(|w| [w[0], w[1], w[2], w[3]]).collect()
+            }
+            CurveBasis::BSpline => cp
+                .windows(4)
+                .map(|w| {
+                    [
+                        (w[0] + w[1] * 4.0 + w[2]) / 6.0,
+                        (w[1] * 4.0 + w[2] * 2.0) / 6.0,
+                        (w[1] * 2.0 + w[2] * 4.0) / 6.0,
+                        (w[1] + w[2] * 4.0 + w[3]) / 6.0,
+                    ]
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct CurveSegment {
+    cp: [Point3; 4],
+    u_range: (f32, f32), // parametric range of this piece along the whole curve
+    width: (f32, f32),
+    mode: CurveMode,
+}
+
+impl CurveSegment {
+    fn bounding_box(&self) -> AABB {
+        let half_width = 0.5 * self.width.0.max(self.width.1);
+        let pad = Vec3::new(half_width, half_width, half_width);
+        let (lo, hi) = bounds(&self.cp);
+
+        AABB::new(lo - pad, hi + pad)
+    }
+}
+
+// A set of curves sharing one material, e.g. a groom of hair or a patch of grass, with a BVH
+// over all of their segments.
+#[derive(Clone)]
+pub struct Curves<'a> {
+    segments: Vec<CurveSegment>,
+    bvh: IndexedBVH,
+    material: Surface<'a>,
+}
+
+impl<'a> Curves<'a> {
+    pub fn new(curves: &[Curve], material: Surface<'a>) -> Self {
+        let mut segments = Vec::new();
+
+        for curve in curves {
+            // Pre-split each cubic into quarters so the BVH bounds stay tight
+            let pieces: Vec<[Point3; 4]> = curve
+                .bezier_segments()
+                .iter()
+                .flat_map(|cp| {
+                    let (a, b) = split_bezier(cp);
+                    let (a0, a1) = split_bezier(&a);
+                    let (b0, b1) = split_bezier(&b);
+                    vec![a0, a1, b0, b1]
+                })
+                .collect();
+
+            let count = pieces.len() as f32;
+            for (i, cp) in pieces.into_iter().enumerate() {
+                let u_range = (i as f32 / count, (i + 1) as f32 / count);
+                let width = (
+                    lerp(u_range.0, curve.width0, curve.width1),
+                    lerp(u_range.1, curve.width0, curve.width1),
+                );
+
+                segments.push(CurveSegment { cp, u_range, width, mode: curve.mode });
+            }
+        }
+
+        let boxes: Vec<AABB> = segments.iter().map(|s| s.bounding_box()).collect();
+        let bvh = IndexedBVH::new(&boxes);
+
+        Curves { segments, bvh, material }
+    }
+
+    // Loads curves from a plain text file with one curve per line:
+    //
+    //     <bezier|bspline> <flat|round> <width0> <width1> x y z x y z ...
+    //
+    // Blank lines and lines starting with '#' are ignored.
+    pub fn load<P: AsRef<Path>>(path: P, material: Surface<'a>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut curves = Vec::new();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", line_number + 1, message),
+                )
+            };
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() < 4 {
+                return Err(invalid("expected basis, mode and widths"));
+            }
+
+            let basis = match tokens[0] {
+                "bezier" => CurveBasis::Bezier,
+                "bspline" => CurveBasis::BSpline,
+                _ => return Err(invalid("unknown curve basis")),
+            };
+            let mode = match tokens[1] {
+                "flat" => CurveMode::Flat,
+                "round" => CurveMode::Round,
+                _ => return Err(invalid("unknown curve mode")),
+            };
+
+            let numbers = tokens[2..]
+                .iter()
+                .map(|t| t.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| invalid("malformed number"))?;
+
+            let coordinates = &numbers[2..];
+            if coordinates.len() % 3 != 0 {
+                return Err(invalid("control points must have three coordinates"));
+            }
+
+            let control_points: Vec<Point3> =
+                coordinates.chunks(3).map(|c| Point3::new(c[0], c[1], c[2])).collect();
+            if !basis.accepts(control_points.len()) {
+                return Err(invalid("wrong number of control points for curve basis"));
+            }
+
+            curves.push(Curve::new(control_points, basis, mode, numbers[0], numbers[1]));
+        }
+
+        Ok(Curves::new(&curves, material))
+    }
+
+    fn hit_segment(
+        &self,
+        segment: &CurveSegment,
+        r: &Ray,
+        tmin: f32,
+        tmax: f32,
+    ) -> Option<HitRecord<'_>> {
+        // Work in a coordinate system where the ray starts at the origin and points down +z
+        let ray_length = r.direction().length();
+        let direction = *r.direction() / ray_length;
+        let frame = ONB::new(&direction);
+        let to_ray_space = |p: &Point3| {
+            let q = *p - *r.origin();
+            Vec3::new(q.dot(frame.u()), q.dot(frame.v()), q.dot(frame.w()))
+        };
+        let cp = [
+            to_ray_space(&segment.cp[0]),
+            to_ray_space(&segment.cp[1]),
+            to_ray_space(&segment.cp[2]),
+            to_ray_space(&segment.cp[3]),
+        ];
+
+        // Subdivide until each piece is close enough to a line segment, relative to its width
+        let mut l0: f32 = 0.0;
+        for i in 0..2 {
+            let d = cp[i] - cp[i + 1] * 2.0 + cp[i + 2];
+            l0 = l0.max(d.x().abs()).max(d.y().abs()).max(d.z().abs());
+        }
+        let epsilon = 0.05 * segment.width.0.max(segment.width.1);
+        let depth = if l0 > 0.0 && epsilon > 0.0 {
+            ((2.0f32.sqrt() * 6.0 * l0 / (8.0 * epsilon)).log2() as i32 / 2)
+                .clamp(0, MAX_SUBDIVISION_DEPTH)
+        } else {
+            0
+        };
+
+        let (z, u) = recursive_intersect(
+            &cp,
+            (0.0, 1.0),
+            segment.width,
+            depth,
+            tmin * ray_length,
+            tmax * ray_length,
+        )?;
+
+        let t = z / ray_length;
+        let p = r.at(t);
+        let center = eval_bezier(&segment.cp, u);
+        let dpdu = bezier_derivative(&segment.cp, u);
+        let tangent = if dpdu.near_zero() { *frame.u() } else { vec3::unit_vector(&dpdu) };
+
+        // Flat ribbons face the ray; round ones bend the normal across the width like a cylinder
+        let facing = -(direction - tangent * direction.dot(&tangent));
+        let facing = if facing.near_zero() { *frame.v() } else { vec3::unit_vector(&facing) };
+        let bitangent = tangent.cross(&facing);
+        let half_width = 0.5 * lerp(u, segment.width.0, segment.width.1);
+        let offset = (p - center).dot(&bitangent);
+        let s = if half_width > 0.0 { clamp(offset / half_width, -1.0, 1.0) } else { 0.0 };
+
+        let normal = match segment.mode {
+            CurveMode::Flat => facing,
+            CurveMode::Round => facing * (1.0 - s * s).sqrt() + bitangent * s,
+        };
+        let u = lerp(u, segment.u_range.0, segment.u_range.1);
+        let v = 0.5 + 0.5 * s;
+        let front_face = HitRecord::face_normal(r, &normal);
+
+        let mut rec = if front_face {
+            HitRecord::new(p, normal, t, u, v, front_face, &self.material)
+        } else {
+            HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+        };
+        // Per unit of the whole curve's u, which only spans `u_range` over this segment
+        rec.dpdu = dpdu / (segment.u_range.1 - segment.u_range.0);
+        rec.dpdv = bitangent * (2.0 * half_width);
+
+        Some(rec)
+    }
+}
+
+impl Hittable for Curves<'_> {
+    fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        self.bvh
+            .hit(r, tmin, tmax, |i, tmin, tmax| self.hit_segment(&self.segments[i], r, tmin, tmax))
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> Option<AABB> {
+        self.bvh.bounding_box()
+    }
+}
+
+// Returns the ray-space depth and segment-local parameter of the closest hit. Control points are
+// in ray space, so a hit means the curve passes within half its width of the z-axis.
+fn recursive_intersect(
+    cp: &[Vec3; 4],
+    u_range: (f32, f32),
+    width: (f32, f32),
+    depth: i32,
+    zmin: f32,
+    zmax: f32,
+) -> Option<(f32, f32)> {
+    let (u0, u1) = u_range;
+    let half_width = 0.5 * lerp(u0, width.0, width.1).max(lerp(u1, width.0, width.1));
+    let (lo, hi) = bounds(cp);
+
+    if lo.x() - half_width > 0.0
+        || hi.x() + half_width < 0.0
+        || lo.y() - half_width > 0.0
+        || hi.y() + half_width < 0.0
+        || lo.z() - half_width > zmax
+        || hi.z() + half_width < zmin
+    {
+        return None;
+    }
+
+    if depth > 0 {
+        let (first_half, second_half) = split_bezier(cp);
+        let u_mid = 0.5 * (u0 + u1);
+
+        let first = recursive_intersect(&first_half, (u0, u_mid), width, depth - 1, zmin, zmax);
+        let zmax = first.map_or(zmax, |(z, _)| z);
+        let second = recursive_intersect(&second_half, (u_mid, u1), width, depth - 1, zmin, zmax);
+
+        return second.or(first);
+    }
+
+    // Reject hits beyond either end of the (now nearly linear) piece
+    let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+    if edge < 0.0 {
+        return None;
+    }
+    let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+    if edge < 0.0 {
+        return None;
+    }
+
+    // Closest point on the chord to the z-axis
+    let sx = cp[3].x() - cp[0].x();
+    let sy = cp[3].y() - cp[0].y();
+    let denom = sx * sx + sy * sy;
+    if denom == 0.0 {
+        return None;
+    }
+
+    let w = (-cp[0].x() * sx - cp[0].y() * sy) / denom;
+    let u = clamp(lerp(w, u0, u1), u0, u1);
+    let hit_width = lerp(u, width.0, width.1);
+    let pc = eval_bezier(cp, clamp(w, 0.0, 1.0));
+
+    if pc.x() * pc.x() + pc.y() * pc.y() > 0.25 * hit_width * hit_width
+        || pc.z() < zmin
+        || pc.z() > zmax
+    {
+        None
+    } else {
+        Some((pc.z(), u))
+    }
+}
+
+fn split_bezier(cp: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let p01 = (cp[0] + cp[1]) * 0.5;
+    let p12 = (cp[1] + cp[2]) * 0.5;
+    let p23 = (cp[2] + cp[3]) * 0.5;
+    let p012 = (p01 + p12) * 0.5;
+    let p123 = (p12 + p23) * 0.5;
+    let mid = (p012 + p123) * 0.5;
+
+    ([cp[0], p01, p012, mid], [mid, p123, p23, cp[3]])
+}
+
+fn eval_bezier(cp: &[Vec3; 4], u: f32) -> Vec3 {
+    let s = 1.0 - u;
+    cp[0] * (s * s * s)
+        + cp[1] * (3.0 * s * s * u)
+        + cp[2] * (3.0 * s * u * u)
+        + cp[3] * (u * u * u)
+}
+
+fn bezier_derivative(cp: &[Vec3; 4], u: f32) -> Vec3 {
+    let s = 1.0 - u;
+    (cp[1] - cp[0]) * (3.0 * s * s)
+        + (cp[2] - cp[1]) * (6.0 * s * u)
+        + (cp[3] - cp[2]) * (3.0 * u * u)
+}
+
+fn bounds(cp: &[Vec3; 4]) -> (Vec3, Vec3) {
+    cp.iter().fold(
+        (
+            Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            -Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        ),
+        |(lo, hi), p| {
+            (
+                Vec3::new(lo.x().min(p.x()), lo.y().min(p.y()), lo.z().min(p.z())),
+                Vec3::new(hi.x().max(p.x()), hi.y().max(p.y()), hi.z().max(p.z())),
+            )
+        },
+    )
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    (1.0 - t) * a + t * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Color;
+
+    #[test]
+    fn tangent_spans_the_whole_curve() {
+        let straight = Curve::new(
+            (0..4).map(|i| Point3::new(-2.0 + 4.0 * i as f32 / 3.0, 0.0, 0.0)).collect(),
+            CurveBasis::Bezier,
+            CurveMode::Flat,
+            0.2,
+            0.2,
+        );
+        let curves = Curves::new(&[straight], Surface::Metal(Color::new(0.8, 0.8, 0.8), 0.0));
+
+        // Split into pieces internally, but u still runs once over the 4 units of the curve
+        let r = Ray::new(Point3::new(0.3, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = curves.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.dpdu - Vec3::new(4.0, 0.0, 0.0)).length() < 1e-3);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    material::Surface, phase::PhaseFunction, ray::Ray, texture::SurfaceTexture,
+    utility::random_double, vec3::Color,
+};
+
+// Participating medium filling the free space of a scene, everywhere outside dielectrics. Its
+// density is `density` at height `base` and falls off exponentially above it at rate `falloff`,
+// so zero falloff gives homogeneous fog. It attenuates every ray, and with scattering enabled
+// scatters light once along each path instead, lighting up shafts from area lights.
+#[derive(Copy, Clone)]
+pub struct Fog {
+    pub density: f32,
+    pub base: f32,
+    pub falloff: f32,
+    pub albedo: Color,
+    pub phase: PhaseFunction,
+    pub scattering: bool,
+}
+
+impl Fog {
+    pub fn homogeneous(density: f32, albedo: Color) -> Self {
+        Fog {
+            density,
+            base: 0.0,
+            falloff: 0.0,
+            albedo,
+            phase: PhaseFunction::Isotropic,
+            scattering: false,
+        }
+    }
+
+    pub fn height(density: f32, base: f32, falloff: f32, albedo: Color) -> Self {
+        Fog { base, falloff, ..Fog::homogeneous(density, albedo) }
+    }
+
+    pub fn phase(mut self, phase: PhaseFunction) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    pub fn scattering(mut self, scattering: bool) -> Self {
+        self.scattering = scattering;
+        self
+    }
+
+    // Optical depth along `r` from its origin to parameter `t`, in closed form
+    pub fn optical_depth(&self, r: &Ray, t: f32) -> f32 {
+        let (scale, rate) = self.along(r);
+
+        if rate.abs() < 1e-6 {
+            scale * t
+        } else {
+            scale * (1.0 - (-rate * t).exp()) / rate
+        }
+    }
+
+    pub fn transmittance(&self, r: &Ray, t: f32) -> f32 {
+        (-self.optical_depth(r, t)).exp()
+    }
+
+    // Parameter along `r` of a scattering event before `tmax`, if one happens, found by
+    // inverting the optical depth
+    pub fn sample(&self, r: &Ray, tmax: f32) -> Option<f32> {
+        let depth = -(1.0 - random_double(&mut rand::thread_rng())).ln();
+        let (scale, rate) = self.along(r);
+
+        let t = if rate.abs() < 1e-6 {
+            depth / scale
+        } else {
+            // Rays rising out of the fog may never accumulate that much depth
+            let remaining = 1.0 - depth * rate / scale;
+            if remaining <= 0.0 {
+                return None;
+            }
+            -remaining.ln() / rate
+        };
+
+        if t < tmax {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    pub fn phase_function(&self) -> Surface<'static> {
+        Surface::Medium(SurfaceTexture::Solid(self.albedo), self.phase)
+    }
+
+    // Density per unit of the ray's parameter at its origin, and its exponential rate of change
+    fn along(&self, r: &Ray) -> (f32, f32) {
+        let height = r.origin().y() - self.base;
+        let scale = self.density * r.direction().length() * (-self.falloff * height).exp();
+
+        (scale, self.falloff * r.direction().y())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::{Point3, Vec3};
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    // Optical depth by the midpoint rule, from the density at each point along the ray
+    fn integrated_depth(fog: &Fog, r: &Ray, t: f32) -> f32 {
+        let steps = 10000;
+        let dt = t / steps as f32;
+        (0..steps)
+            .map(|i| {
+                let p = r.at((i as f32 + 0.5) * dt);
+                let density = fog.density * (-fog.falloff * (p.y() - fog.base)).exp();
+                density * r.direction().length() * dt
+            })
+            .sum()
+    }
+
+    #[test]
+    fn homogeneous_depth_grows_with_distance() {
+        let fog = Fog::homogeneous(0.2, white());
+        let r = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(3.0, 0.0, 4.0), 0.0);
+
+        assert!(close(fog.optical_depth(&r, 2.0), 0.2 * 5.0 * 2.0, 1e-5));
+        assert!(close(fog.transmittance(&r, 2.0), (-2.0f32).exp(), 1e-5));
+    }
+
+    #[test]
+    fn height_depth_matches_integral() {
+        let fog = Fog::height(0.5, 1.0, 0.7, white());
+        let rays = [
+            Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.5, 0.0), 0.0),
+            Ray::new(Point3::new(0.0, 6.0, 0.0), Vec3::new(0.0, -2.0, 1.0), 0.0),
+            Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 1.0), 0.0),
+        ];
+
+        for r in rays {
+            let (closed, integrated) =
+                (fog.optical_depth(&r, 2.5), integrated_depth(&fog, &r, 2.5));
+            assert!(close(closed, integrated, 1e-3 * integrated.max(1.0)));
+        }
+    }
+
+    // Fraction of sampled scattering events before `tmax`
+    fn scatter_rate(fog: &Fog, r: &Ray, tmax: f32) -> f32 {
+        let trials = 20000;
+        (0..trials).filter(|_| fog.sample(r, tmax).is_some()).count() as f32 / trials as f32
+    }
+
+    #[test]
+    fn sampling_inverts_the_optical_depth() {
+        let rays = [
+            (Fog::homogeneous(0.3, white()), Vec3::new(1.0, 0.0, 0.0)),
+            (Fog::height(0.8, 0.0, 0.5, white()), Vec3::new(1.0, 0.8, 0.0)),
+            (Fog::height(0.8, 0.0, 0.5, white()), Vec3::new(1.0, -0.3, 0.0)),
+        ];
+
+        for (fog, direction) in rays {
+            let r = Ray::new(Point3::new(0.0, 1.0, 0.0), direction, 0.0);
+            let expected = 1.0 - fog.transmittance(&r, 3.0);
+            assert!(close(scatter_rate(&fog, &r, 3.0), expected, 0.02));
+
+            // Every event lies within the segment
+            for _ in 0..1000 {
+                if let Some(t) = fog.sample(&r, 3.0) {
+                    assert!((0.0..3.0).contains(&t));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rising_rays_can_leave_the_fog() {
+        // Total optical depth upwards is density / falloff at the origin's height
+        let fog = Fog::height(0.5, 0.0, 1.0, white());
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+
+        assert!(close(fog.optical_depth(&r, f32::INFINITY), 0.5, 1e-5));
+        assert!(close(scatter_rate(&fog, &r, f32::INFINITY), 1.0 - (-0.5f32).exp(), 0.02));
+    }
+}
@@ -0,0 +1,589 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    aabb::AABB,
+    ray::Ray,
+    utility::{random_double, INFINITY},
+    vec3::{self, Point3, Vec3},
+};
+
+// Scalar values on a regular 3D lattice of `nx * ny * nz` voxels filling the box from `min` to
+// `max`, stored with x varying fastest. Lookups interpolate trilinearly between voxel centers.
+#[derive(Clone)]
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f32>,
+    min: Point3,
+    max: Point3,
+}
+
+impl VoxelGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f32>, min: Point3, max: Point3) -> Self {
+        assert_eq!(data.len(), nx * ny * nz, "Voxel data doesn't match the grid resolution");
+        VoxelGrid { nx, ny, nz, data, min, max }
+    }
+
+    // Fills each voxel with `f` at its center
+    pub fn from_fn(
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        min: Point3,
+        max: Point3,
+        f: impl Fn(&Point3) -> f32,
+    ) -> Self {
+        let size = max - min;
+        let mut data = Vec::with_capacity(nx * ny * nz);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    let p = min
+                        + Vec3::new(
+                            size.x() * (i as f32 + 0.5) / nx as f32,
+                            size.y() * (j as f32 + 0.5) / ny as f32,
+                            size.z() * (k as f32 + 0.5) / nz as f32,
+                        );
+                    data.push(f(&p));
+                }
+            }
+        }
+
+        VoxelGrid::new(nx, ny, nz, data, min, max)
+    }
+
+    // Reads a grid in Mitsuba's binary `.vol` format, all little-endian:
+    //   bytes 0-2   "VOL", then the version byte 3
+    //   int32       encoding, 1 for float32 data
+    //   int32 x 3   resolution in x, y and z
+    //   int32       channels per voxel; several are averaged into one
+    //   float32 x 6 bounds: minimum x, y, z, then maximum x, y, z
+    //   float32     voxel data, x fastest, then y, z and channel innermost
+    pub fn load_vol<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+            return Err(invalid("not a version 3 .vol file"));
+        }
+
+        let int = |offset: usize| {
+            i32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let float = |offset: usize| f32::from_bits(int(offset) as u32);
+
+        if int(4) != 1 {
+            return Err(invalid("only float32 voxel data is supported"));
+        }
+
+        let (nx, ny, nz, channels) = (int(8), int(12), int(16), int(20));
+        if nx <= 0 || ny <= 0 || nz <= 0 || channels <= 0 {
+            return Err(invalid("resolution and channel count must be positive"));
+        }
+        let (nx, ny, nz, channels) = (nx as usize, ny as usize, nz as usize, channels as usize);
+
+        let min = Point3::new(float(24), float(28), float(32));
+        let max = Point3::new(float(36), float(40), float(44));
+
+        let count = nx * ny * nz;
+        if bytes.len() < 48 + 4 * count * channels {
+            return Err(invalid("file is shorter than its resolution requires"));
+        }
+
+        let data = (0..count)
+            .map(|voxel| {
+                let first = 48 + 4 * voxel * channels;
+                (0..channels).map(|c| float(first + 4 * c)).sum::<f32>() / channels as f32
+            })
+            .collect();
+
+        Ok(VoxelGrid::new(nx, ny, nz, data, min, max))
+    }
+
+    // Reads headerless little-endian float32 voxels, x fastest, of the given resolution
+    pub fn load_raw<P: AsRef<Path>>(
+        path: P,
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        min: Point3,
+        max: Point3,
+    ) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() != 4 * nx * ny * nz {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file size doesn't match the grid resolution",
+            ));
+        }
+
+        let data =
+            bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+
+        Ok(VoxelGrid::new(nx, ny, nz, data, min, max))
+    }
+
+    // Same voxels stretched to fill another box; rotate or move the medium with the instances
+    pub fn with_bounds(mut self, min: Point3, max: Point3) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn bounding_box(&self) -> AABB {
+        AABB::new(self.min, self.max)
+    }
+
+    pub fn max_value(&self) -> f32 {
+        self.data.iter().cloned().fold(0.0, f32::max)
+    }
+
+    fn voxel(&self, i: usize, j: usize, k: usize) -> f32 {
+        self.data[(k * self.ny + j) * self.nx + i]
+    }
+
+    // Interpolated value at `p`, zero outside the grid
+    pub fn value(&self, p: &Point3) -> f32 {
+        let size = self.max - self.min;
+        let local = *p - self.min;
+        let (x, y, z) = (local.x() / size.x(), local.y() / size.y(), local.z() / size.z());
+        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) || !(0.0..=1.0).contains(&z) {
+            return 0.0;
+        }
+
+        // Continuous voxel coordinates, with voxel centers at whole numbers
+        let axis = |t: f32, n: usize| {
+            let c = (t * n as f32 - 0.5).max(0.0).min((n - 1) as f32);
+            let lower = (c.floor() as usize).min(n.saturating_sub(2));
+            let upper = (lower + 1).min(n - 1);
+            (lower, upper, c - lower as f32)
+        };
+        let (i0, i1, fx) = axis(x, self.nx);
+        let (j0, j1, fy) = axis(y, self.ny);
+        let (k0, k1, fz) = axis(z, self.nz);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let front = lerp(
+            lerp(self.voxel(i0, j0, k0), self.voxel(i1, j0, k0), fx),
+            lerp(self.voxel(i0, j1, k0), self.voxel(i1, j1, k0), fx),
+            fy,
+        );
+        let back = lerp(
+            lerp(self.voxel(i0, j0, k1), self.voxel(i1, j0, k1), fx),
+            lerp(self.voxel(i0, j1, k1), self.voxel(i1, j1, k1), fx),
+            fy,
+        );
+
+        lerp(front, back, fz)
+    }
+}
+
+// Coarse grid of upper bounds on a density, so tracking can take long steps through thin regions
+// rather than stepping everywhere at the densest voxel's rate
+#[derive(Clone)]
+pub struct MajorantGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    values: Vec<f32>,
+    min: Point3,
+    max: Point3,
+}
+
+impl MajorantGrid {
+    // A single bound over the whole box
+    pub fn constant(bounds: &AABB, value: f32) -> Self {
+        MajorantGrid {
+            nx: 1,
+            ny: 1,
+            nz: 1,
+            values: vec![value],
+            min: *bounds.min(),
+            max: *bounds.max(),
+        }
+    }
+
+    // Bounds over blocks of `block` voxels a side, widened by a voxel to cover interpolation
+    pub fn from_voxels(grid: &VoxelGrid, block: usize) -> Self {
+        let coarse = |n: usize| n.div_ceil(block);
+        let (nx, ny, nz) = (coarse(grid.nx), coarse(grid.ny), coarse(grid.nz));
+
+        let mut values = Vec::with_capacity(nx * ny * nz);
+        for ck in 0..nz {
+            for cj in 0..ny {
+                for ci in 0..nx {
+                    let range = |c: usize, n: usize| {
+                        c.saturating_mul(block).saturating_sub(1)..((c + 1) * block + 1).min(n)
+                    };
+
+                    let mut value: f32 = 0.0;
+                    for k in range(ck, grid.nz) {
+                        for j in range(cj, grid.ny) {
+                            for i in range(ci, grid.nx) {
+                                value = value.max(grid.voxel(i, j, k));
+                            }
+                        }
+                    }
+                    values.push(value);
+                }
+            }
+        }
+
+        // Whole blocks may overhang the voxel grid, where the density is zero anyway
+        let voxel_size = grid.max - grid.min;
+        let max = grid.min
+            + Vec3::new(
+                voxel_size.x() * (nx * block) as f32 / grid.nx as f32,
+                voxel_size.y() * (ny * block) as f32 / grid.ny as f32,
+                voxel_size.z() * (nz * block) as f32 / grid.nz as f32,
+            );
+
+        MajorantGrid { nx, ny, nz, values, min: grid.min, max }
+    }
+
+    // Delta tracking along `r` between `start` and `end`: walks the cells the ray crosses,
+    // sampling tentative collisions at each cell's majorant and accepting them with probability
+    // `density / majorant`. Returns the parameter of the first real collision.
+    pub fn delta_track(
+        &self,
+        r: &Ray,
+        start: f32,
+        end: f32,
+        density: impl Fn(&Point3) -> f32,
+    ) -> Option<f32> {
+        let (t0, t1) = AABB::new(self.min, self.max).intersect(r, start, end)?;
+        let t0 = t0.max(start);
+        let t1 = t1.min(end);
+        if t0 >= t1 {
+            return None;
+        }
+
+        let mut rng = rand::thread_rng();
+        let ray_length = r.direction().length();
+        let origin = vec3::unpack(r.origin());
+        let direction = vec3::unpack(r.direction());
+        let min = vec3::unpack(&self.min);
+        let size = vec3::unpack(&(self.max - self.min));
+        let counts = [self.nx, self.ny, self.nz];
+
+        // Cell containing the entry point, and where the ray crosses into the next along each axis
+        let entry = vec3::unpack(&r.at(t0));
+        let mut cell = [0usize; 3];
+        let mut next = [INFINITY; 3];
+        let mut delta = [INFINITY; 3];
+        for axis in 0..3 {
+            let width = size[axis] / counts[axis] as f32;
+            let index = ((entry[axis] - min[axis]) / width).floor();
+            cell[axis] = (index.max(0.0) as usize).min(counts[axis] - 1);
+
+            if direction[axis] > 0.0 {
+                let plane = min[axis] + (cell[axis] + 1) as f32 * width;
+                next[axis] = (plane - origin[axis]) / direction[axis];
+                delta[axis] = width / direction[axis];
+            } else if direction[axis] < 0.0 {
+                let plane = min[axis] + cell[axis] as f32 * width;
+                next[axis] = (plane - origin[axis]) / direction[axis];
+                delta[axis] = -width / direction[axis];
+            }
+        }
+
+        let mut t = t0;
+        loop {
+            let axis = if next[0] < next[1] && next[0] < next[2] {
+                0
+            } else if next[1] < next[2] {
+                1
+            } else {
+                2
+            };
+            let cell_end = next[axis].min(t1);
+
+            let majorant = self.values[(cell[2] * self.ny + cell[1]) * self.nx + cell[0]];
+            if majorant > 0.0 {
+                loop {
+                    t -= (1.0 - random_double(&mut rng)).ln() / (majorant * ray_length);
+                    if t >= cell_end {
+                        break;
+                    }
+                    if random_double(&mut rng) * majorant < density(&r.at(t)) {
+                        return Some(t);
+                    }
+                }
+            }
+
+            // Exponential steps are memoryless, so restart at the cell boundary
+            t = cell_end;
+            if t >= t1 {
+                return None;
+            }
+
+            next[axis] += delta[axis];
+            if direction[axis] > 0.0 {
+                cell[axis] += 1;
+                if cell[axis] >= counts[axis] {
+                    return None;
+                }
+            } else {
+                if cell[axis] == 0 {
+                    return None;
+                }
+                cell[axis] -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    // 2 x 2 x 2 voxels over the unit cube, each holding its own index
+    fn counting_grid() -> VoxelGrid {
+        let data = (0..8).map(|i| i as f32).collect();
+        VoxelGrid::new(2, 2, 2, data, Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn value_at_voxel_centers() {
+        let grid = counting_grid();
+        assert!(close(grid.value(&Point3::new(0.25, 0.25, 0.25)), 0.0, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.75, 0.25, 0.25)), 1.0, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.25, 0.75, 0.25)), 2.0, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.25, 0.25, 0.75)), 4.0, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.75, 0.75, 0.75)), 7.0, 1e-6));
+    }
+
+    #[test]
+    fn value_interpolates_and_clamps() {
+        let grid = counting_grid();
+        // Halfway between every pair of centers is the mean of all eight
+        assert!(close(grid.value(&Point3::new(0.5, 0.5, 0.5)), 3.5, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.5, 0.25, 0.25)), 0.5, 1e-6));
+        // Past the outermost centers the nearest voxel holds
+        assert!(close(grid.value(&Point3::new(0.0, 0.0, 0.0)), 0.0, 1e-6));
+        assert!(close(grid.value(&Point3::new(1.0, 1.0, 1.0)), 7.0, 1e-6));
+    }
+
+    #[test]
+    fn value_outside_is_zero() {
+        let grid = counting_grid();
+        assert_eq!(grid.value(&Point3::new(1.5, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.value(&Point3::new(0.5, -0.1, 0.5)), 0.0);
+    }
+
+    #[test]
+    fn from_fn_samples_voxel_centers() {
+        let min = Point3::new(-1.0, 0.0, 0.0);
+        let grid = VoxelGrid::from_fn(4, 1, 1, min, Point3::new(1.0, 1.0, 1.0), |p| p.x());
+        assert!(close(grid.value(&Point3::new(-0.75, 0.5, 0.5)), -0.75, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.25, 0.5, 0.5)), 0.25, 1e-6));
+        assert!(close(grid.max_value(), 0.75, 1e-6));
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("raytrace-rs-{}-{}", std::process::id(), name))
+    }
+
+    // Header and data of a float32 .vol file
+    fn vol_bytes(resolution: [i32; 3], channels: i32, bounds: [f32; 6], data: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL".to_vec();
+        bytes.push(3);
+        for int in [1, resolution[0], resolution[1], resolution[2], channels] {
+            bytes.extend_from_slice(&int.to_le_bytes());
+        }
+        for float in bounds.iter().chain(data) {
+            bytes.extend_from_slice(&float.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn load_vol_round_trip() {
+        let path = temp_path("round-trip.vol");
+        let data: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        fs::write(&path, vol_bytes([2, 2, 2], 1, [0.0, 0.0, 0.0, 1.0, 1.0, 1.0], &data)).unwrap();
+
+        let grid = VoxelGrid::load_vol(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let expected = counting_grid();
+        for p in [Point3::new(0.25, 0.25, 0.25), Point3::new(0.6, 0.3, 0.9)] {
+            assert!(close(grid.value(&p), expected.value(&p), 1e-6));
+        }
+    }
+
+    #[test]
+    fn load_vol_averages_channels_and_reads_bounds() {
+        let path = temp_path("channels.vol");
+        let data = [1.0, 3.0, 5.0, 7.0];
+        fs::write(&path, vol_bytes([2, 1, 1], 2, [-1.0, 0.0, 0.0, 1.0, 2.0, 3.0], &data)).unwrap();
+
+        let grid = VoxelGrid::load_vol(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(close(grid.value(&Point3::new(-0.5, 1.0, 1.5)), 2.0, 1e-6));
+        assert!(close(grid.value(&Point3::new(0.5, 1.0, 1.5)), 6.0, 1e-6));
+        assert_eq!(grid.value(&Point3::new(0.5, 2.5, 1.5)), 0.0);
+    }
+
+    #[test]
+    fn load_vol_rejects_malformed_files() {
+        let bounds = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let cases = [
+            ("magic.vol", {
+                let mut bytes = vol_bytes([1, 1, 1], 1, bounds, &[1.0]);
+                bytes[0] = b'X';
+                bytes
+            }),
+            ("encoding.vol", {
+                let mut bytes = vol_bytes([1, 1, 1], 1, bounds, &[1.0]);
+                bytes[4] = 2;
+                bytes
+            }),
+            ("resolution.vol", vol_bytes([0, 1, 1], 1, bounds, &[])),
+            ("short.vol", vol_bytes([2, 2, 2], 1, bounds, &[1.0, 2.0])),
+            ("header.vol", b"VOL".to_vec()),
+        ];
+
+        for (name, bytes) in cases {
+            let path = temp_path(name);
+            fs::write(&path, bytes).unwrap();
+            let result = VoxelGrid::load_vol(&path);
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(
+                result.err().map(|e| e.kind()),
+                Some(io::ErrorKind::InvalidData),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn load_raw_round_trip() {
+        let path = temp_path("round-trip.raw");
+        let bytes: Vec<u8> = (0..8).flat_map(|i| (i as f32).to_le_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+
+        let (min, max) = (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let grid = VoxelGrid::load_raw(&path, 2, 2, 2, min, max).unwrap();
+        let mismatched = VoxelGrid::load_raw(&path, 2, 2, 3, min, max);
+        fs::remove_file(&path).unwrap();
+
+        let expected = counting_grid();
+        for p in [Point3::new(0.75, 0.25, 0.75), Point3::new(0.4, 0.55, 0.1)] {
+            assert!(close(grid.value(&p), expected.value(&p), 1e-6));
+        }
+        assert_eq!(mismatched.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn missing_files_are_not_found() {
+        let path = temp_path("missing.vol");
+        let (min, max) = (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        assert_eq!(
+            VoxelGrid::load_vol(&path).err().map(|e| e.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+        assert_eq!(
+            VoxelGrid::load_raw(&path, 1, 1, 1, min, max).err().map(|e| e.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn majorants_bound_the_voxels() {
+        let grid = VoxelGrid::from_fn(
+            5,
+            4,
+            3,
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            |p| p.x() * p.y() + p.z(),
+        );
+        let majorants = MajorantGrid::from_voxels(&grid, 2);
+        assert_eq!((majorants.nx, majorants.ny, majorants.nz), (3, 2, 2));
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let p = Point3::new(
+                random_double(&mut rng),
+                random_double(&mut rng),
+                random_double(&mut rng),
+            );
+            let size = majorants.max - majorants.min;
+            let cell =
+                |t: f32, extent: f32, n: usize| ((t / extent * n as f32) as usize).min(n - 1);
+            let (i, j, k) = (
+                cell(p.x(), size.x(), majorants.nx),
+                cell(p.y(), size.y(), majorants.ny),
+                cell(p.z(), size.z(), majorants.nz),
+            );
+            let bound = majorants.values[(k * majorants.ny + j) * majorants.nx + i];
+            assert!(grid.value(&p) <= bound + 1e-6);
+        }
+    }
+
+    // Fraction of delta-tracked rays getting through without a collision
+    fn escape_rate(majorants: &MajorantGrid, r: &Ray, density: impl Fn(&Point3) -> f32) -> f32 {
+        let trials = 20000;
+        let escaped = (0..trials)
+            .filter(|_| majorants.delta_track(r, 0.0, INFINITY, &density).is_none())
+            .count();
+
+        escaped as f32 / trials as f32
+    }
+
+    #[test]
+    fn delta_track_matches_homogeneous_transmittance() {
+        let (min, max) = (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let grid = VoxelGrid::from_fn(4, 4, 4, min, max, |_| 1.5);
+        let majorants = MajorantGrid::from_voxels(&grid, 2);
+
+        // Diagonally through several cells, with a direction that isn't of unit length
+        let r = Ray::new(Point3::new(-1.0, 0.0, 0.1), Vec3::new(2.0, 0.8, 0.6), 0.0);
+        let (t0, t1) = AABB::new(min, max).intersect(&r, 0.0, INFINITY).unwrap();
+        let length = (t1 - t0) * r.direction().length();
+
+        let expected = (-1.5 * length).exp();
+        assert!(close(escape_rate(&majorants, &r, |p| grid.value(p)), expected, 0.02));
+    }
+
+    #[test]
+    fn delta_track_matches_varying_transmittance() {
+        // Density rising linearly along the ray, under a loose bound: optical depth 1/2
+        let bounds = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let majorants = MajorantGrid::constant(&bounds, 3.0);
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0), 0.0);
+
+        let expected = (-0.5f32).exp();
+        assert!(close(escape_rate(&majorants, &r, |p| p.x()), expected, 0.02));
+    }
+
+    #[test]
+    fn delta_track_collides_inside_the_segment() {
+        let bounds = AABB::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let majorants = MajorantGrid::constant(&bounds, 5.0);
+        let r = Ray::new(Point3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+
+        for _ in 0..1000 {
+            if let Some(t) = majorants.delta_track(&r, 1.2, 1.8, |_| 5.0) {
+                assert!((1.2..=1.8).contains(&t));
+            }
+        }
+        assert!(majorants.delta_track(&r, 2.5, INFINITY, |_| 5.0).is_none());
+    }
+}
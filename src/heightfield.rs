@@ -0,0 +1,276 @@
+use image::{ImageBuffer, Pixel, Rgb};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    material::Surface,
+    mesh::hit_triangle,
+    perlin::Perlin,
+    ray::Ray,
+    vec3::{self, Point3, Vec3},
+};
+
+// Terrain over a regular grid of height samples, spanning `size.x() x size.z()` from `corner`
+// with heights in [0, size.y()]. Each grid cell is split into two triangles.
+#[derive(Clone)]
+pub struct Heightfield<'a> {
+    heights: Vec<f32>, // world-space heights, row-major with x varying fastest
+    normals: Vec<Vec3>,
+    cell_bounds: Vec<(f32, f32)>, // min/max height per cell for early rejection
+    nx: usize,
+    nz: usize,
+    corner: Point3,
+    cell_x: f32,
+    cell_z: f32,
+    bbox: AABB,
+    material: Surface<'a>,
+}
+
+impl<'a> Heightfield<'a> {
+    // `samples` are normalized heights in [0, 1], `nx` by `nz` of them.
+    pub fn new(
+        samples: Vec<f32>,
+        nx: usize,
+        nz: usize,
+        corner: Point3,
+        size: Vec3,
+        material: Surface<'a>,
+    ) -> Self {
+        assert!(nx >= 2 && nz >= 2 && samples.len() == nx * nz, "Invalid heightfield dimensions");
+
+        let heights: Vec<f32> = samples.iter().map(|h| corner.y() + h * size.y()).collect();
+        let cell_x = size.x() / (nx - 1) as f32;
+        let cell_z = size.z() / (nz - 1) as f32;
+
+        // Smooth vertex normals from central differences of the height samples
+        let height = |i: usize, j: usize| heights[j * nx + i];
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let dhdx = (height(i1, j) - height(i0, j)) / ((i1 - i0) as f32 * cell_x);
+                let dhdz = (height(i, j1) - height(i, j0)) / ((j1 - j0) as f32 * cell_z);
+
+                normals.push(vec3::unit_vector(&Vec3::new(-dhdx, 1.0, -dhdz)));
+            }
+        }
+
+        let mut cell_bounds = Vec::with_capacity((nx - 1) * (nz - 1));
+        let mut min_height = f32::INFINITY;
+        let mut max_height = -f32::INFINITY;
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let corners =
+                    [height(i, j), height(i + 1, j), height(i, j + 1), height(i + 1, j + 1)];
+                let lo = corners.iter().cloned().fold(f32::INFINITY, f32::min);
+                let hi = corners.iter().cloned().fold(-f32::INFINITY, f32::max);
+
+                min_height = min_height.min(lo);
+                max_height = max_height.max(hi);
+                cell_bounds.push((lo, hi));
+            }
+        }
+
+        // Pad flat fields so the box never degenerates
+        let bbox = AABB::new(
+            Point3::new(corner.x(), min_height - 0.0001, corner.z()),
+            Point3::new(corner.x() + size.x(), max_height + 0.0001, corner.z() + size.z()),
+        );
+
+        Heightfield {
+            heights,
+            normals,
+            cell_bounds,
+            nx,
+            nz,
+            corner,
+            cell_x,
+            cell_z,
+            bbox,
+            material,
+        }
+    }
+
+    // Heights are taken from the luminance of each pixel; the top row of the image maps to
+    // the far (max z) edge of the field.
+    pub fn from_image(
+        buffer: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+        corner: Point3,
+        size: Vec3,
+        material: Surface<'a>,
+    ) -> Self {
+        let (nx, nz) = (buffer.width() as usize, buffer.height() as usize);
+        let mut samples = Vec::with_capacity(nx * nz);
+
+        for j in 0..nz {
+            for i in 0..nx {
+                let pixel = buffer.get_pixel(i as u32, (nz - 1 - j) as u32);
+                let rgb = pixel.channels();
+                let luminance =
+                    0.2126 * rgb[0] as f32 + 0.7152 * rgb[1] as f32 + 0.0722 * rgb[2] as f32;
+
+                samples.push(luminance / 255.0);
+            }
+        }
+
+        Heightfield::new(samples, nx, nz, corner, size, material)
+    }
+
+    // Samples `Perlin::turb` over a `resolution x resolution` grid; `frequency` scales the
+    // noise domain relative to the unit square.
+    pub fn from_noise(
+        noise: &Perlin,
+        resolution: usize,
+        frequency: f32,
+        corner: Point3,
+        size: Vec3,
+        material: Surface<'a>,
+    ) -> Self {
+        let mut samples = Vec::with_capacity(resolution * resolution);
+        for j in 0..resolution {
+            for i in 0..resolution {
+                let x = i as f32 / (resolution - 1) as f32;
+                let z = j as f32 / (resolution - 1) as f32;
+                samples.push(noise.turb(&(Point3::new(x, 0.0, z) * frequency)));
+            }
+        }
+
+        let max = samples.iter().cloned().fold(0.0, f32::max);
+        if max > 0.0 {
+            samples.iter_mut().for_each(|h| *h /= max);
+        }
+
+        Heightfield::new(samples, resolution, resolution, corner, size, material)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        Point3::new(
+            self.corner.x() + i as f32 * self.cell_x,
+            self.heights[j * self.nx + i],
+            self.corner.z() + j as f32 * self.cell_z,
+        )
+    }
+
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let p00 = self.vertex(i, j);
+        let p10 = self.vertex(i + 1, j);
+        let p01 = self.vertex(i, j + 1);
+        let p11 = self.vertex(i + 1, j + 1);
+
+        let n00 = self.normals[j * self.nx + i];
+        let n10 = self.normals[j * self.nx + i + 1];
+        let n01 = self.normals[(j + 1) * self.nx + i];
+        let n11 = self.normals[(j + 1) * self.nx + i + 1];
+
+        let first = hit_triangle(r, &p00, &p10, &p11, tmin, tmax).map(|(t, b1, b2)| {
+            (t, n00 * (1.0 - b1 - b2) + n10 * b1 + n11 * b2, p10 - p00, p11 - p00)
+        });
+        let tmax = first.map_or(tmax, |(t, ..)| t);
+        let second = hit_triangle(r, &p00, &p11, &p01, tmin, tmax).map(|(t, b1, b2)| {
+            (t, n00 * (1.0 - b1 - b2) + n11 * b1 + n01 * b2, p11 - p00, p01 - p00)
+        });
+
+        let (t, normal, e1, e2) = second.or(first)?;
+        let p = r.at(t);
+        let u = (p.x() - self.corner.x()) / (self.cell_x * (self.nx - 1) as f32);
+        let v = (p.z() - self.corner.z()) / (self.cell_z * (self.nz - 1) as f32);
+
+        // Sidedness comes from the flat triangle, shading from the interpolated normal
+        let geometric_normal = vec3::unit_vector(&e2.cross(&e1));
+        let front_face = HitRecord::face_normal(r, &geometric_normal);
+        let normal = vec3::unit_vector(&normal);
+
+        let mut rec = if front_face {
+            HitRecord::new(p, normal, t, u, v, front_face, &self.material)
+        } else {
+            HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+        };
+        rec.geometric_normal = if front_face { geometric_normal } else { -geometric_normal };
+
+        // u and v follow x and z, with height changing along the triangle's plane
+        let size_x = self.cell_x * (self.nx - 1) as f32;
+        let size_z = self.cell_z * (self.nz - 1) as f32;
+        let slope_x = -geometric_normal.x() / geometric_normal.y();
+        let slope_z = -geometric_normal.z() / geometric_normal.y();
+        rec.dpdu = Vec3::new(size_x, slope_x * size_x, 0.0);
+        rec.dpdv = Vec3::new(0.0, slope_z * size_z, size_z);
+
+        Some(rec)
+    }
+}
+
+impl Hittable for Heightfield<'_> {
+    // 2D DDA over the grid cells the ray passes through (in xz), testing only the cells whose
+    // height range overlaps the ray's height range across the cell.
+    fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let (t0, t1) = self.bbox.intersect(r, tmin, tmax)?;
+
+        let origin = *r.origin();
+        let direction = *r.direction();
+        let cells_x = self.nx - 1;
+        let cells_z = self.nz - 1;
+
+        let start = r.at(t0);
+        let gx = ((start.x() - self.corner.x()) / self.cell_x).max(0.0);
+        let gz = ((start.z() - self.corner.z()) / self.cell_z).max(0.0);
+        let mut i = (gx as usize).min(cells_x - 1);
+        let mut j = (gz as usize).min(cells_z - 1);
+
+        // Parametric distance between successive cell boundaries, and to the first boundary
+        let axis_setup = |d: f32, o: f32, corner: f32, cell: f32, idx: usize| {
+            if d > 0.0 {
+                let next = corner + (idx + 1) as f32 * cell;
+                ((next - o) / d, cell / d, 1)
+            } else if d < 0.0 {
+                let next = corner + idx as f32 * cell;
+                ((next - o) / d, -cell / d, -1)
+            } else {
+                (f32::INFINITY, f32::INFINITY, 0)
+            }
+        };
+        let (mut next_x, delta_x, step_x) =
+            axis_setup(direction.x(), origin.x(), self.corner.x(), self.cell_x, i);
+        let (mut next_z, delta_z, step_z) =
+            axis_setup(direction.z(), origin.z(), self.corner.z(), self.cell_z, j);
+
+        let mut t_enter = t0;
+        loop {
+            let t_exit = next_x.min(next_z).min(t1);
+
+            let y_enter = origin.y() + t_enter * direction.y();
+            let y_exit = origin.y() + t_exit * direction.y();
+            let (cell_min, cell_max) = self.cell_bounds[j * cells_x + i];
+
+            if y_enter.min(y_exit) <= cell_max && y_enter.max(y_exit) >= cell_min {
+                if let Some(rec) = self.hit_cell(r, i, j, tmin.max(t_enter - 0.0001), t1) {
+                    return Some(rec);
+                }
+            }
+
+            if t_exit >= t1 {
+                return None;
+            }
+
+            if next_x < next_z {
+                if (step_x < 0 && i == 0) || (step_x > 0 && i + 1 >= cells_x) {
+                    return None;
+                }
+                i = (i as i32 + step_x) as usize;
+                next_x += delta_x;
+            } else {
+                if (step_z < 0 && j == 0) || (step_z > 0 && j + 1 >= cells_z) {
+                    return None;
+                }
+                j = (j as i32 + step_z) as usize;
+                next_z += delta_z;
+            }
+
+            t_enter = t_exit;
+        }
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
@@ -3,25 +3,34 @@ use rand::thread_rng;
 use crate::{
     aabb::{surrounding_box, AABB},
     bvh::BVHNode,
-    instances::{FlipFace, RotateY, Translate},
+    curve::Curves,
+    heightfield::Heightfield,
+    instances::{Cutout, FlipFace, RotateY, Translate},
     material::Surface,
+    mesh::TriangleMesh,
     ray::Ray,
     rect::{Box, XYRect, XZRect, YZRect},
+    sdf::SdfObject,
     sphere::{MovingSphere, Sphere},
     utility::random_int_range,
-    vec3::{Point3, Vec3},
-    volumes::Constant,
+    vec3::{self, Color, Point3, Vec3},
+    volumes::{Constant, Heterogeneous},
 };
 
 #[derive(Copy, Clone)]
 pub struct HitRecord<'a> {
     pub p: Point3,
-    pub normal: Vec3,
+    pub normal: Vec3, // shading normal, against the incident ray
     pub material: &'a Surface<'a>,
     pub t: f32,
     pub u: f32,
     pub v: f32,
     pub front_face: bool,
+    pub dpdu: Vec3, // surface tangent along u; zero for primitives that don't provide one
+    pub dpdv: Vec3,
+    pub geometric_normal: Vec3, // true surface normal, on the same side as `normal`
+    pub emission: Color,        // given off at a collision inside an emissive medium
+    pub object: Option<&'a HitModel<'a>>, // outermost model hit, with any instancing around it
 }
 
 impl<'a> HitRecord<'a> {
@@ -34,7 +43,31 @@ impl<'a> HitRecord<'a> {
         front_face: bool,
         material: &'a Surface,
     ) -> Self {
-        HitRecord { p, normal, material, t, u, v, front_face }
+        HitRecord {
+            p,
+            normal,
+            material,
+            t,
+            u,
+            v,
+            front_face,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            geometric_normal: normal,
+            emission: Color::default(),
+            object: None,
+        }
+    }
+
+    // Partial derivatives of the surface position, or an arbitrary frame around the shading
+    // normal for primitives without a parameterization
+    pub fn tangents(&self) -> (Vec3, Vec3) {
+        if self.dpdu.near_zero() || self.dpdv.near_zero() {
+            let frame = vec3::coordinate_system(&self.normal);
+            (*frame.u(), *frame.v())
+        } else {
+            (self.dpdu, self.dpdv)
+        }
     }
 
     // Returns true if ray is incident from outside surface, false if from inside surface
@@ -53,6 +86,10 @@ pub trait Hittable: Send + Sync {
     fn random(&self, origin: &Vec3) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    // Whether this emits light and can be sampled as a light through `pdf_value` and `random`
+    fn is_light(&self) -> bool {
+        false
+    }
 }
 // Wrapper class to avoid dealing with trait objects
 #[derive(Clone)]
@@ -67,12 +104,18 @@ pub enum HitModel<'a> {
     Translate(Translate<'a>),
     RotateY(RotateY<'a>),
     FlipFace(FlipFace<'a>),
+    Cutout(Cutout<'a>),
     Constant(Constant<'a>),
+    Heterogeneous(Heterogeneous<'a>),
+    Sdf(SdfObject<'a>),
+    Heightfield(Heightfield<'a>),
+    Curves(Curves<'a>),
+    Mesh(TriangleMesh<'a>),
 }
 
 impl Hittable for HitModel<'_> {
     fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        match self {
+        let rec = match self {
             Self::Sphere(sphere) => sphere.hit(r, tmin, tmax),
             Self::MovingSphere(sphere) => sphere.hit(r, tmin, tmax),
             Self::BVH(bvh) => bvh.hit(r, tmin, tmax),
@@ -83,7 +126,19 @@ impl Hittable for HitModel<'_> {
             Self::Translate(translate) => translate.hit(r, tmin, tmax),
             Self::RotateY(rotate) => rotate.hit(r, tmin, tmax),
             Self::FlipFace(rotate) => rotate.hit(r, tmin, tmax),
+            Self::Cutout(cutout) => cutout.hit(r, tmin, tmax),
             Self::Constant(volume) => volume.hit(r, tmin, tmax),
+            Self::Heterogeneous(volume) => volume.hit(r, tmin, tmax),
+            Self::Sdf(sdf) => sdf.hit(r, tmin, tmax),
+            Self::Heightfield(terrain) => terrain.hit(r, tmin, tmax),
+            Self::Curves(curves) => curves.hit(r, tmin, tmax),
+            Self::Mesh(mesh) => mesh.hit(r, tmin, tmax),
+        }?;
+
+        // Hierarchies pass on the object hit inside them
+        match self {
+            Self::BVH(_) => Some(rec),
+            _ => Some(HitRecord { object: Some(self), ..rec }),
         }
     }
 
@@ -99,7 +154,13 @@ impl Hittable for HitModel<'_> {
             Self::Translate(translate) => translate.bounding_box(t0, t1),
             Self::RotateY(rotate) => rotate.bounding_box(t0, t1),
             Self::FlipFace(rotate) => rotate.bounding_box(t0, t1),
+            Self::Cutout(cutout) => cutout.bounding_box(t0, t1),
             Self::Constant(volume) => volume.bounding_box(t0, t1),
+            Self::Heterogeneous(volume) => volume.bounding_box(t0, t1),
+            Self::Sdf(sdf) => sdf.bounding_box(t0, t1),
+            Self::Heightfield(terrain) => terrain.bounding_box(t0, t1),
+            Self::Curves(curves) => curves.bounding_box(t0, t1),
+            Self::Mesh(mesh) => mesh.bounding_box(t0, t1),
         }
     }
 
@@ -115,7 +176,13 @@ impl Hittable for HitModel<'_> {
             Self::Translate(translate) => translate.pdf_value(origin, v),
             Self::RotateY(rotate) => rotate.pdf_value(origin, v),
             Self::FlipFace(rotate) => rotate.pdf_value(origin, v),
+            Self::Cutout(cutout) => cutout.pdf_value(origin, v),
             Self::Constant(volume) => volume.pdf_value(origin, v),
+            Self::Heterogeneous(volume) => volume.pdf_value(origin, v),
+            Self::Sdf(sdf) => sdf.pdf_value(origin, v),
+            Self::Heightfield(terrain) => terrain.pdf_value(origin, v),
+            Self::Curves(curves) => curves.pdf_value(origin, v),
+            Self::Mesh(mesh) => mesh.pdf_value(origin, v),
         }
     }
 
@@ -131,7 +198,45 @@ impl Hittable for HitModel<'_> {
             Self::Translate(translate) => translate.random(origin),
             Self::RotateY(rotate) => rotate.random(origin),
             Self::FlipFace(rotate) => rotate.random(origin),
+            Self::Cutout(cutout) => cutout.random(origin),
             Self::Constant(volume) => volume.random(origin),
+            Self::Heterogeneous(volume) => volume.random(origin),
+            Self::Sdf(sdf) => sdf.random(origin),
+            Self::Heightfield(terrain) => terrain.random(origin),
+            Self::Curves(curves) => curves.random(origin),
+            Self::Mesh(mesh) => mesh.random(origin),
+        }
+    }
+
+    fn is_light(&self) -> bool {
+        match self {
+            Self::Sphere(sphere) => sphere.is_light(),
+            Self::XYRect(rect) => rect.is_light(),
+            Self::XZRect(rect) => rect.is_light(),
+            Self::YZRect(rect) => rect.is_light(),
+            Self::Box(_box) => _box.is_light(),
+            Self::Translate(translate) => translate.is_light(),
+            Self::RotateY(rotate) => rotate.is_light(),
+            Self::FlipFace(rotate) => rotate.is_light(),
+            Self::Cutout(cutout) => cutout.is_light(),
+            Self::Mesh(mesh) => mesh.is_light(),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> HitModel<'a> {
+    // Adds every part of this model that can be sampled as a light to `lights`, looking inside
+    // bounding volume hierarchies
+    pub fn collect_lights(&self, lights: &mut HittableList<HitModel<'a>>) {
+        if self.is_light() {
+            lights.add(self.clone());
+        } else if let Self::BVH(bvh) = self {
+            let (left, right) = bvh.children();
+            left.collect_lights(lights);
+            if !std::ptr::eq(left, right) {
+                right.collect_lights(lights);
+            }
         }
     }
 }
@@ -216,3 +321,123 @@ impl<T: Hittable> Hittable for HittableList<T> {
             .random(origin)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        texture::SurfaceTexture,
+        utility::{random_unit_vector, INFINITY},
+    };
+
+    fn glowing<'a>() -> Surface<'a> {
+        Surface::DiffuseLight(SurfaceTexture::Solid(Color::new(4.0, 4.0, 4.0)))
+    }
+
+    fn matte<'a>() -> Surface<'a> {
+        Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    // A glowing sphere among plain ones in a hierarchy, a moved panel, a turned lamp and a wall
+    fn world<'a>() -> HittableList<HitModel<'a>> {
+        let mut spheres: Vec<Arc<HitModel>> = (0..6)
+            .map(|i| {
+                let material = if i == 4 { glowing() } else { matte() };
+                Arc::new(HitModel::Sphere(Sphere::new(
+                    Point3::new(3.0 * i as f32, 0.0, -6.0),
+                    1.0,
+                    material,
+                )))
+            })
+            .collect();
+        let count = spheres.len();
+
+        let mut world = HittableList::new();
+        world.add(HitModel::BVH(BVHNode::new(&mut spheres, 0, count, 0, 1)));
+        world.add(HitModel::Translate(Translate::new(
+            HitModel::XZRect(XZRect::new(-1.0, 1.0, -1.0, 1.0, 0.0, glowing())),
+            Vec3::new(0.0, 5.0, 0.0),
+        )));
+        world.add(HitModel::RotateY(RotateY::new(
+            HitModel::Box(Box::new(
+                Point3::new(3.0, 0.0, 0.0),
+                Point3::new(4.0, 1.0, 2.0),
+                glowing(),
+            )),
+            30.0,
+        )));
+        world.add(HitModel::XYRect(XYRect::new(-5.0, 5.0, 0.0, 5.0, -10.0, matte())));
+
+        world
+    }
+
+    // A point a little above the middle of `light`, from where it covers much of the sky
+    fn above(light: &HitModel) -> Point3 {
+        let bbox = light.bounding_box(0.0, 1.0).unwrap();
+        (*bbox.min() + *bbox.max()) / 2.0 + Vec3::new(0.2, 2.5, 0.1)
+    }
+
+    fn collect<'a>(world: &HittableList<HitModel<'a>>) -> HittableList<HitModel<'a>> {
+        let mut lights = HittableList::new();
+        for object in world.objects() {
+            object.collect_lights(&mut lights);
+        }
+
+        lights
+    }
+
+    #[test]
+    fn collects_emitters_through_instances_and_hierarchies() {
+        let lights = collect(&world());
+        assert_eq!(lights.objects().len(), 3);
+        assert!(lights.objects().iter().all(|light| !matches!(light, HitModel::BVH(_))));
+    }
+
+    #[test]
+    fn collected_lights_sample_where_they_are_drawn() {
+        let lights = collect(&world());
+
+        for light in lights.objects() {
+            let origin = above(light);
+            for _ in 0..200 {
+                let direction = light.random(&origin);
+                assert!(light.hit(&Ray::new(origin, direction, 0.0), 0.001, INFINITY).is_some());
+
+                let pdf = light.pdf_value(&origin, &direction);
+                assert!(pdf.is_finite() && pdf > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn collected_light_pdfs_integrate_to_one() {
+        let lights = collect(&world());
+        let mut rng = thread_rng();
+
+        for light in lights.objects() {
+            let origin = above(light);
+            let samples = 200_000;
+            let total: f64 = (0..samples)
+                .map(|_| light.pdf_value(&origin, &random_unit_vector(&mut rng)) as f64)
+                .sum();
+            let integral = total * 4.0 * std::f64::consts::PI / samples as f64;
+
+            assert!((integral - 1.0).abs() < 0.05, "integral {}", integral);
+        }
+    }
+
+    #[test]
+    fn sphere_light_from_inside() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, glowing());
+        let origin = Point3::new(0.5, -0.3, 0.2);
+
+        for _ in 0..100 {
+            let direction = sphere.random(&origin);
+            assert!(sphere.hit(&Ray::new(origin, direction, 0.0), 0.001, INFINITY).is_some());
+            let pdf = sphere.pdf_value(&origin, &direction);
+            assert!((pdf - 1.0 / (4.0 * std::f32::consts::PI)).abs() < 1e-6);
+        }
+    }
+}
@@ -2,8 +2,9 @@ use crate::{
     aabb::AABB,
     hittable::{HitModel, HitRecord, Hittable},
     ray::Ray,
-    utility::{degrees_to_radians, INFINITY},
-    vec3::Vec3,
+    texture::SurfaceTexture,
+    utility::{degrees_to_radians, random_double, INFINITY},
+    vec3::{Point3, Vec3},
 };
 
 #[derive(Clone)]
@@ -49,6 +50,19 @@ impl Hittable for Translate<'_> {
             None
         }
     }
+
+    // Directions are unchanged by the move, only the origin they're sampled from
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        self.hit_model.pdf_value(&(*origin - self.offset), v)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.hit_model.random(&(*origin - self.offset))
+    }
+
+    fn is_light(&self) -> bool {
+        self.hit_model.is_light()
+    }
 }
 
 #[derive(Clone)]
@@ -69,7 +83,7 @@ impl<'a> RotateY<'a> {
         let has_box = bbox.is_some();
 
         let mut min: [f32; 3] = [INFINITY; 3];
-        let mut max: [f32; 3] = [INFINITY, -INFINITY, -INFINITY];
+        let mut max: [f32; 3] = [-INFINITY; 3];
 
         if has_box {
             let bbox = bbox.unwrap();
@@ -135,19 +149,28 @@ impl<'a> Hittable for RotateY<'a> {
                 -sin_theta * hit_rec.p.x() + cos_theta * hit_rec.p.z(),
             );
 
-            let new_normal = Vec3::new(
-                cos_theta * hit_rec.normal.x() + sin_theta * hit_rec.normal.z(),
-                hit_rec.normal.y(),
-                -sin_theta * hit_rec.normal.x() + cos_theta * hit_rec.normal.z(),
-            );
+            let rotate = |v: &Vec3| {
+                Vec3::new(
+                    cos_theta * v.x() + sin_theta * v.z(),
+                    v.y(),
+                    -sin_theta * v.x() + cos_theta * v.z(),
+                )
+            };
+            let new_normal = rotate(&hit_rec.normal);
 
             hit_rec.p = new_p;
-            let front_face = HitRecord::face_normal(&rotated_r, &new_normal);
+            hit_rec.dpdu = rotate(&hit_rec.dpdu);
+            hit_rec.dpdv = rotate(&hit_rec.dpdv);
+            hit_rec.geometric_normal = rotate(&hit_rec.geometric_normal);
+
+            // Both normals are world-space now, so they're checked against the world-space ray
+            let front_face = HitRecord::face_normal(r, &new_normal);
             if front_face {
                 hit_rec.normal = new_normal;
                 Some(hit_rec)
             } else {
                 hit_rec.normal = -new_normal;
+                hit_rec.geometric_normal = -hit_rec.geometric_normal;
                 Some(hit_rec)
             }
         } else {
@@ -157,6 +180,37 @@ impl<'a> Hittable for RotateY<'a> {
     fn bounding_box(&self, _: f32, _: f32) -> Option<AABB> {
         self.bbox
     }
+
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        self.hit_model.pdf_value(&self.to_object(origin), &self.to_object(v))
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.to_world(&self.hit_model.random(&self.to_object(origin)))
+    }
+
+    fn is_light(&self) -> bool {
+        self.hit_model.is_light()
+    }
+}
+
+impl RotateY<'_> {
+    // Rotations of points and directions into the unrotated object's space, and back out
+    fn to_object(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() - self.sin_theta * v.z(),
+            v.y(),
+            self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
+
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() + self.sin_theta * v.z(),
+            v.y(),
+            -self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
 }
 
 #[derive(Clone)]
@@ -182,4 +236,88 @@ impl Hittable for FlipFace<'_> {
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.hit_model.bounding_box(t0, t1)
     }
+
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        self.hit_model.pdf_value(origin, v)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.hit_model.random(origin)
+    }
+
+    fn is_light(&self) -> bool {
+        self.hit_model.is_light()
+    }
+}
+
+// Opacity mask over another object. Intersections are kept with probability equal to the
+// opacity at the hit point and otherwise passed through, so partially transparent regions
+// average out over samples; every ray goes through `hit`, including those towards lights.
+#[derive(Clone)]
+pub struct Cutout<'a> {
+    hit_model: Box<HitModel<'a>>,
+    opacity: SurfaceTexture<'a>,
+}
+
+impl<'a> Cutout<'a> {
+    pub fn new(hit_model: HitModel<'a>, opacity: SurfaceTexture<'a>) -> Self {
+        Cutout { hit_model: Box::new(hit_model), opacity }
+    }
+}
+
+impl Hittable for Cutout<'_> {
+    fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let mut rng = rand::thread_rng();
+        let mut tmin = tmin;
+
+        // Continue the ray past each rejected intersection
+        while let Some(rec) = self.hit_model.hit(r, tmin, tmax) {
+            let alpha = self.opacity.alpha(rec.u, rec.v, &rec.p);
+            if alpha >= 1.0 || random_double(&mut rng) < alpha {
+                return Some(rec);
+            }
+            tmin = rec.t + 0.0001;
+        }
+
+        None
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.hit_model.bounding_box(t0, t1)
+    }
+
+    // Sampling ignores the mask, so the density is the wrapped object's; samples landing on
+    // transparent regions are traced on through it
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        self.hit_model.pdf_value(origin, v)
+    }
+
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        self.hit_model.random(origin)
+    }
+
+    fn is_light(&self) -> bool {
+        self.hit_model.is_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Surface, sphere::Sphere, vec3::Color};
+
+    #[test]
+    fn rotated_normals_face_the_incoming_ray() {
+        let diffuse = Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.5, 0.5, 0.5)));
+        let ball = HitModel::Sphere(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, diffuse));
+        let rotated = RotateY::new(ball, 90.0);
+
+        // Runs along z in the sphere's frame, perpendicular to the world-space normal
+        let r = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let rec = rotated.hit(&r, 0.001, INFINITY).unwrap();
+
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-4);
+        assert!((rec.geometric_normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-4);
+    }
 }
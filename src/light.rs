@@ -0,0 +1,286 @@
+use crate::{
+    hittable::{HitModel, HittableList},
+    onb::ONB,
+    utility::{degrees_to_radians, random_to_sphere, INFINITY, PI},
+    vec3::{self, Color, Point3, Vec3},
+};
+
+// Receivers closer than this to a point or spot light have no direction to it
+const MIN_DIST_SQUARED: f32 = 1e-8;
+
+// Lights without geometry, which rays can never hit; the integrator reaches them with shadow
+// rays instead. Intensities are colors, so `color::blackbody` gives physically tinted lamps.
+#[derive(Copy, Clone)]
+pub enum Light {
+    // Radiant intensity from a point, or from a sphere of `radius` for soft shadows, sampled
+    // over the part of it facing the receiver
+    Point { position: Point3, intensity: Color, radius: f32 },
+    // Point light shining within a cone around `direction`, fading smoothly from full intensity
+    // inside `cos_inner` to none outside `cos_outer`, both cosines of the half-angle
+    Spot { position: Point3, direction: Vec3, intensity: Color, cos_inner: f32, cos_outer: f32 },
+    // Irradiance arriving along `direction` from infinitely far, spread over a cone whose
+    // half-angle has cosine `cos_angle` for a sun of nonzero size
+    Directional { direction: Vec3, irradiance: Color, cos_angle: f32 },
+}
+
+impl Light {
+    pub fn point(position: Point3, intensity: Color, radius: f32) -> Self {
+        Self::Point { position, intensity, radius }
+    }
+
+    // Cone of half-angle `angle` degrees aimed at `target`, its edge fading over `falloff` degrees
+    pub fn spot(
+        position: Point3,
+        target: Point3,
+        intensity: Color,
+        angle: f32,
+        falloff: f32,
+    ) -> Self {
+        Self::Spot {
+            position,
+            direction: vec3::unit_vector(&(target - position)),
+            intensity,
+            cos_inner: degrees_to_radians((angle - falloff).max(0.0)).cos(),
+            cos_outer: degrees_to_radians(angle).cos(),
+        }
+    }
+
+    // Light travelling along `direction`, from a disc `angle` degrees in radius across the sky
+    pub fn directional(direction: Vec3, irradiance: Color, angle: f32) -> Self {
+        Self::Directional {
+            direction: vec3::unit_vector(&direction),
+            irradiance,
+            cos_angle: degrees_to_radians(angle).cos(),
+        }
+    }
+
+    // Sampled unit direction from `p` towards the light, the distance to it, and the light
+    // arriving from there already divided by the probability of the sample. None where the
+    // light doesn't reach.
+    pub fn sample(&self, p: &Point3) -> Option<(Vec3, f32, Color)> {
+        let mut rng = rand::thread_rng();
+
+        match self {
+            Self::Point { position, intensity, radius } => {
+                let offset = *position - *p;
+                let dist_squared = offset.length_squared();
+                if dist_squared < MIN_DIST_SQUARED {
+                    return None;
+                }
+                let distance = dist_squared.sqrt();
+                if *radius <= 0.0 {
+                    return Some((offset / distance, distance, *intensity / dist_squared));
+                }
+                if dist_squared <= radius * radius {
+                    return None;
+                }
+
+                // Uniformly over the cone the sphere subtends, up to its near side. Its radiance
+                // is the intensity over its projected area, gathered over the cone's solid angle.
+                let frame = ONB::new(&offset);
+                let towards = frame.local_vec(&random_to_sphere(&mut rng, *radius, dist_squared));
+                let along = towards.dot(&offset);
+                let distance =
+                    along - (along * along - dist_squared + radius * radius).max(0.0).sqrt();
+
+                let ratio = radius * radius / dist_squared;
+                let solid_angle = 2.0 * PI * ratio / (1.0 + (1.0 - ratio).sqrt());
+
+                Some((towards, distance, *intensity * solid_angle / (PI * radius * radius)))
+            }
+            Self::Spot { position, direction, intensity, cos_inner, cos_outer } => {
+                let offset = *position - *p;
+                let dist_squared = offset.length_squared();
+                if dist_squared < MIN_DIST_SQUARED {
+                    return None;
+                }
+                let distance = dist_squared.sqrt();
+                let towards = offset / distance;
+
+                let cos_theta = (-towards).dot(direction);
+                if cos_theta <= *cos_outer {
+                    return None;
+                }
+
+                // Smoothstep across the edge of the cone
+                let edge = if cos_theta >= *cos_inner {
+                    1.0
+                } else {
+                    let x = (cos_theta - cos_outer) / (cos_inner - cos_outer);
+                    x * x * (3.0 - 2.0 * x)
+                };
+
+                Some((towards, distance, *intensity * edge / dist_squared))
+            }
+            Self::Directional { direction, irradiance, cos_angle } => {
+                let sin_angle = (1.0 - cos_angle * cos_angle).max(0.0).sqrt();
+                let towards = if sin_angle > 0.0 {
+                    let frame = ONB::new(&-*direction);
+                    frame.local_vec(&random_to_sphere(&mut rng, sin_angle, 1.0))
+                } else {
+                    -*direction
+                };
+
+                Some((towards, INFINITY, *irradiance))
+            }
+        }
+    }
+}
+
+// Everything the integrator samples directly: emissive geometry through `HittablePDF`, and
+// lights without geometry through shadow rays
+pub struct Lights<'a> {
+    pub geometry: HittableList<HitModel<'a>>,
+    pub delta: Vec<Light>,
+}
+
+impl<'a> Lights<'a> {
+    pub fn new() -> Self {
+        Lights { geometry: HittableList::new(), delta: Vec::new() }
+    }
+
+    pub fn with_delta(mut self, delta: Vec<Light>) -> Self {
+        self.delta = delta;
+        self
+    }
+
+    // Every emitter in `world` that can be sampled, including those inside instances, cutouts and
+    // bounding volume hierarchies: spheres, rectangles, boxes and triangle meshes. Emissive moving
+    // spheres, SDFs, heightfields and curves can't be sampled and are left to be found by chance.
+    pub fn from_world(world: &HittableList<HitModel<'a>>) -> Self {
+        let mut lights = Lights::new();
+        for object in world.objects().iter() {
+            object.collect_lights(&mut lights.geometry);
+        }
+
+        lights
+    }
+}
+
+impl Default for Lights<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    fn gray(value: f32) -> Color {
+        Color::new(value, value, value)
+    }
+
+    // Spot 10 above the origin pointing down, full out to 20 degrees and dark past 30
+    fn spot() -> Light {
+        Light::spot(
+            Point3::new(0.0, 10.0, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            gray(100.0),
+            30.0,
+            10.0,
+        )
+    }
+
+    // Point on the ground seen from the spot at `degrees` off its axis
+    fn ground_at(degrees: f32) -> Point3 {
+        Point3::new(10.0 * degrees_to_radians(degrees).tan(), 0.0, 0.0)
+    }
+
+    #[test]
+    fn spot_is_full_inside_its_inner_cone() {
+        for degrees in [0.0, 10.0, 19.5] {
+            let p = ground_at(degrees);
+            let (towards, distance, incident) = spot().sample(&p).unwrap();
+            let expected = Point3::new(0.0, 10.0, 0.0) - p;
+
+            assert!(close(distance, expected.length(), 1e-4));
+            assert!(close(towards.dot(&vec3::unit_vector(&expected)), 1.0, 1e-5));
+            assert!(close(incident.x(), 100.0 / expected.length_squared(), 1e-4));
+        }
+    }
+
+    #[test]
+    fn spot_is_dark_outside_its_outer_cone() {
+        assert!(spot().sample(&ground_at(30.5)).is_none());
+        assert!(spot().sample(&Point3::new(0.0, 20.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn spot_fades_smoothly_across_its_edge() {
+        let falloff = |degrees: f32| {
+            let p = ground_at(degrees);
+            let (_, distance, incident) = spot().sample(&p).unwrap_or((p, 1.0, gray(0.0)));
+            incident.x() * distance * distance / 100.0
+        };
+
+        // Halfway between the cosines of the cone's edges the smoothstep is at a half
+        let (cos_inner, cos_outer) =
+            (degrees_to_radians(20.0).cos(), degrees_to_radians(30.0).cos());
+        let halfway = (0.5 * (cos_inner + cos_outer)).acos().to_degrees();
+        assert!(close(falloff(halfway), 0.5, 1e-3));
+
+        let mut previous = 1.0;
+        for step in 0..=20 {
+            let value = falloff(20.0 + step as f32 * 0.5);
+            assert!(value <= previous + 1e-6 && (0.0..=1.0).contains(&value));
+            previous = value;
+        }
+        assert!(previous < 1e-3);
+    }
+
+    #[test]
+    fn point_light_falls_off_with_the_square_of_distance() {
+        let light = Light::point(Point3::new(0.0, 4.0, 0.0), gray(32.0), 0.0);
+        let (towards, distance, incident) = light.sample(&Point3::new(0.0, 0.0, 0.0)).unwrap();
+
+        assert!(close(towards.y(), 1.0, 1e-6));
+        assert!(close(distance, 4.0, 1e-6));
+        assert!(close(incident.x(), 2.0, 1e-6));
+    }
+
+    #[test]
+    fn spherical_light_samples_its_visible_cap() {
+        let center = Point3::new(0.0, 4.0, 0.0);
+        let light = Light::point(center, gray(32.0), 0.5);
+        let p = Point3::new(0.0, 0.0, 0.0);
+
+        for _ in 0..1000 {
+            let (towards, distance, incident) = light.sample(&p).unwrap();
+            // On the near side of the sphere
+            let hit = p + towards * distance;
+            assert!(close((hit - center).length(), 0.5, 1e-3));
+            assert!((hit - center).dot(&(p - center)) > 0.0);
+            // The same as a point light this far away, to within the sphere's size
+            assert!(close(incident.x(), 2.0, 0.05));
+        }
+
+        assert!(light.sample(&Point3::new(0.0, 4.2, 0.0)).is_none());
+    }
+
+    #[test]
+    fn directional_light_comes_from_within_its_disc() {
+        let light = Light::directional(Vec3::new(0.0, -1.0, 0.0), gray(3.0), 5.0);
+        for _ in 0..1000 {
+            let (towards, distance, incident) = light.sample(&Point3::new(1.0, 2.0, 3.0)).unwrap();
+            assert!(towards.y() >= degrees_to_radians(5.0).cos() - 1e-5);
+            assert!(close(towards.length(), 1.0, 1e-5));
+            assert_eq!(distance, INFINITY);
+            assert_eq!(incident.x(), 3.0);
+        }
+    }
+
+    #[test]
+    fn receivers_at_the_light_get_nothing() {
+        let position = Point3::new(1.0, 2.0, 3.0);
+        let point = Light::point(position, gray(10.0), 0.0);
+        assert!(point.sample(&position).is_none());
+
+        let spot = Light::spot(position, Point3::new(1.0, 0.0, 3.0), gray(10.0), 30.0, 10.0);
+        assert!(spot.sample(&position).is_none());
+    }
+}
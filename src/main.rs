@@ -8,29 +8,43 @@ pub mod aabb;
 pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod curve;
+pub mod fog;
+pub mod grid;
+pub mod heightfield;
 pub mod hittable;
 pub mod instances;
+pub mod light;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
 pub mod onb;
 pub mod pdf;
 pub mod perlin;
+pub mod phase;
+pub mod principled;
 pub mod ray;
 pub mod rect;
 pub mod scenes;
+pub mod sdf;
 pub mod sphere;
+pub mod subdivision;
 pub mod texture;
+pub mod thinfilm;
 pub mod utility;
 pub mod vec3;
 pub mod volumes;
 
-use hittable::{HitModel, Hittable, HittableList};
+use fog::Fog;
+use hittable::{HitModel, HitRecord, Hittable, HittableList};
+use light::Lights;
 use material::Material;
+use medium::MediumStack;
 use ray::Ray;
 use vec3::{Color, Vec3};
 
-use crate::{
-    color::process_color, material::Surface, rect::XZRect, sphere::Sphere, texture::SurfaceTexture,
-};
+use crate::color::process_color;
 use utility::*;
 
 // Image dimensions
@@ -40,50 +54,169 @@ const IMG_HEIGHT: u32 = (IMG_WIDTH as f32 / ASPECT_RATIO) as u32;
 const SAMPLES_PER_PIXEL: i32 = 100;
 const MAX_DEPTH: i32 = 50;
 
-fn ray_color<'a>(
+fn ray_color<'a, 'w>(
     r: Ray,
     background: Color,
-    world: &HittableList<HitModel<'a>>,
-    lights: &HittableList<HitModel<'a>>,
+    fog: Option<Fog>,
+    world: &'w HittableList<HitModel<'a>>,
+    lights: &Lights<'a>,
+    media: MediumStack<'w>,
     depth: i32,
 ) -> Color {
     // Limit number of ray bounces
     if depth <= 0 {
-        Color::new(0.0, 0.0, 0.0)
-    } else {
-        if let Some(hit_rec) = world.hit(&r, 0.001, INFINITY) {
-            let emitted = hit_rec.material.emit(&r, &hit_rec, hit_rec.u, hit_rec.v, &hit_rec.p);
-            if let Some(srec) = hit_rec.material.scatter(&r, &hit_rec) {
-                if srec.is_specular {
-                    return srec.attenuation
-                        * ray_color(
-                            srec.specular_ray.unwrap(),
-                            background,
-                            world,
-                            lights,
-                            depth - 1,
-                        );
-                }
-
-                let p0 = PDF::Hittable(HittablePDF::new(&hit_rec.p, lights));
-                let p1 = srec.pdf.unwrap_or(PDF::Uniform(UniformPDF::new(&hit_rec.normal)));
-                let mixed_pdf = MixturePDF::new(p0, p1);
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let surface_hit = world.hit(&r, 0.001, INFINITY);
+    let distance = surface_hit.map_or(INFINITY, |rec| rec.t);
+
+    // Inside a dielectric its interior fills the space, otherwise any fog does; either may
+    // scatter the ray before it reaches the next surface. The scene's fog is still handed on down
+    // the path from inside a dielectric, for whenever it comes back out.
+    let medium = media.current();
+    let segment_fog = if medium.is_none() { fog } else { None };
+    let collision = match (medium, segment_fog) {
+        (Some(dielectric), _) => dielectric
+            .interior
+            .and_then(|interior| Some((interior.sample(&r, distance)?, interior.phase_function()))),
+        (None, Some(fog)) if fog.scattering => {
+            fog.sample(&r, distance).map(|t| (t, fog.phase_function()))
+        }
+        _ => None,
+    };
+
+    // Fog scatters once per path, and only attenuates after that
+    let fog = match (collision, segment_fog) {
+        (Some(_), Some(_)) => fog.map(|fog| fog.scattering(false)),
+        _ => fog,
+    };
+
+    let scatterer;
+    let (hit_rec, surface_hit) = match (collision, surface_hit) {
+        (Some((t, phase_function)), _) => {
+            scatterer = phase_function;
+            let normal = Vec3::new(1.0, 0.0, 0.0);
+            (HitRecord::new(r.at(t), normal, t, 0.0, 0.0, true, &scatterer), None)
+        }
+        (None, Some(rec)) => (rec, Some(rec)),
+        (None, None) => {
+            return match segment_fog {
+                Some(fog) if !fog.scattering => background * fog.transmittance(&r, INFINITY),
+                _ => background,
+            };
+        }
+    };
+
+    // Absorption along the segment travelled through the current medium, or through fog that
+    // doesn't scatter; scattering fog is accounted for by sampling collisions
+    let transmittance = match (medium, segment_fog) {
+        (Some(dielectric), _) => dielectric.transmittance(hit_rec.t * r.direction().length()),
+        (None, Some(fog)) if !fog.scattering => {
+            Color::new(1.0, 1.0, 1.0) * fog.transmittance(&r, hit_rec.t)
+        }
+        _ => Color::new(1.0, 1.0, 1.0),
+    };
+
+    // Surfaces of dielectrics hidden inside a higher-priority medium don't interact; the ray
+    // passes straight through into or out of them
+    if let Some(rec) = surface_hit {
+        if rec.material.dielectric().is_some() && !media.is_interface(rec.material) {
+            let through = Ray::new(rec.p, *r.direction(), r.time());
+            let media = media.cross(rec.material, rec.front_face);
+            return transmittance
+                * ray_color(through, background, fog, world, lights, media, depth - 1);
+        }
+    }
+
+    // Emission first, as preparing the hit strips `Surface::Emissive`
+    let emitted =
+        hit_rec.material.emit(&r, &hit_rec, hit_rec.u, hit_rec.v, &hit_rec.p) + hit_rec.emission;
+    let hit_rec = material::prepare(&hit_rec);
+
+    // Dielectrics refract against whatever medium the ray is actually in or going into
+    let nested;
+    let hit_rec = match surface_hit {
+        Some(rec) if rec.material.dielectric().is_some() => {
+            nested = hit_rec.material.surrounded_by(media.outside_index(rec.material));
+            HitRecord { material: &nested, ..hit_rec }
+        }
+        _ => hit_rec,
+    };
+
+    // Media the scattered ray travels through, having crossed the surface if it was transmitted
+    let media_after = |scattered: &Ray| match surface_hit {
+        Some(rec) if scattered.direction().dot(&rec.geometric_normal) < 0.0 => {
+            media.cross(rec.material, rec.front_face)
+        }
+        _ => media,
+    };
 
+    let radiance = if let Some(srec) = hit_rec.material.scatter(&r, &hit_rec) {
+        if srec.is_specular {
+            let scattered = srec.specular_ray.unwrap();
+            let media = media_after(&scattered);
+            emitted
+                + srec.attenuation
+                    * ray_color(scattered, background, fog, world, lights, media, depth - 1)
+        } else {
+            let direct = direct_light(&r, &hit_rec, fog, world, lights);
+
+            // Aim half the samples at emissive geometry, when the scene has any
+            let p1 = srec.pdf.unwrap_or(PDF::Uniform(UniformPDF::new(&hit_rec.normal)));
+            let (scattered, pdf) = if lights.geometry.objects().is_empty() {
+                let scattered = Ray::new(hit_rec.p, p1.generate(), r.time());
+                let pdf = p1.value(scattered.direction());
+                (scattered, pdf)
+            } else {
+                let p0 = PDF::Hittable(HittablePDF::new(&hit_rec.p, &lights.geometry));
+                let mixed_pdf = MixturePDF::new(p0, p1);
                 let scattered = Ray::new(hit_rec.p, mixed_pdf.generate(), r.time());
                 let pdf = mixed_pdf.value(scattered.direction());
-
-                emitted
-                    + srec.attenuation // 'reflectance'
-                        * hit_rec.material.scattering_pdf(&r, &hit_rec, &scattered)
-                        * ray_color(scattered, background, world, lights, depth - 1)
-                        / pdf
+                (scattered, pdf)
+            };
+            if pdf <= 0.0 {
+                emitted + direct
             } else {
+                let media = media_after(&scattered);
                 emitted
+                    + direct
+                    + hit_rec.material.eval(&r, &hit_rec, &scattered)
+                        * ray_color(scattered, background, fog, world, lights, media, depth - 1)
+                        / pdf
             }
-        } else {
-            background
+        }
+    } else {
+        emitted
+    };
+
+    transmittance * radiance
+}
+
+// Light reaching a non-specular hit straight from lights without geometry, along shadow rays
+// attenuated by any fog
+fn direct_light<'a>(
+    r: &Ray,
+    hit_rec: &HitRecord,
+    fog: Option<Fog>,
+    world: &HittableList<HitModel<'a>>,
+    lights: &Lights<'a>,
+) -> Color {
+    let mut direct = Color::new(0.0, 0.0, 0.0);
+
+    for light in lights.delta.iter() {
+        if let Some((direction, distance, incident)) = light.sample(&hit_rec.p) {
+            let shadow_ray = Ray::new(hit_rec.p, direction, r.time());
+            if world.hit(&shadow_ray, 0.001, distance - 0.001).is_some() {
+                continue;
+            }
+
+            let transmittance = fog.map_or(1.0, |fog| fog.transmittance(&shadow_ray, distance));
+            direct += hit_rec.material.eval(r, hit_rec, &shadow_ray) * incident * transmittance;
         }
     }
+
+    direct
 }
 
 fn main() -> io::Result<()> {
@@ -91,12 +224,13 @@ fn main() -> io::Result<()> {
 
     stream.write_all(format!("P3\n{} {}\n255\n", IMG_WIDTH, IMG_HEIGHT).as_bytes())?;
 
-    // World initialization
+    // World initialization. Scenes lit by lights without geometry, or filled with fog, hand those
+    // back as well, e.g. `let (world, camera, background, delta) = scenes::lamps();` or
+    // `let (world, camera, background, fog) = scenes::final_scene();` with `Some(fog)` below
     let (world, camera, background) = scenes::cornell_box();
-    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(15.0, 15.0, 15.0)));
-    let mut lights = Box::new(HittableList::new());
-    lights.add(HitModel::XZRect(XZRect::new(213.0, 343.0, 227.0, 332.0, 554.0, light)));
-    // lights.add(HitModel::Sphere(Sphere::new(Point3::new(190.0, 90.0, 190.0), 90.0, light)));
+    let delta = Vec::new();
+    let lights = Lights::from_world(&world).with_delta(delta);
+    let fog: Option<Fog> = None;
 
     let t0 = std::time::Instant::now();
     let pb = ProgressBar::new(IMG_HEIGHT.into());
@@ -117,7 +251,15 @@ fn main() -> io::Result<()> {
                             let u = ((i as f32) + rng.gen::<f32>()) / ((IMG_WIDTH - 1) as f32);
                             let v = ((j as f32) + rng.gen::<f32>()) / ((IMG_HEIGHT - 1) as f32);
                             let r = camera.ray_at(u, v);
-                            acc + ray_color(r, background, &world, &lights, MAX_DEPTH)
+                            acc + ray_color(
+                                r,
+                                background,
+                                fog,
+                                &world,
+                                &lights,
+                                MediumStack::new(),
+                                MAX_DEPTH,
+                            )
                         },
                     );
 
@@ -1,13 +1,23 @@
 use rand::thread_rng;
 
+use crate::color;
+use crate::microfacet::{self, fresnel_dielectric, GGX};
+use crate::onb::ONB;
 use crate::pdf::CosinePDF;
+use crate::pdf::MicrofacetPDF;
+use crate::pdf::PhasePDF;
+use crate::pdf::PlasticPDF;
 use crate::pdf::PDF;
+use crate::phase::PhaseFunction;
+use crate::principled::Principled;
 use crate::texture::SurfaceTexture;
 use crate::texture::Texture;
+use crate::thinfilm::ThinFilm;
 use crate::utility::*;
 use crate::vec3;
 use crate::vec3::Point3;
 use crate::vec3::{reflect, refract, unit_vector, Vec3};
+use crate::volumes::{Interior, Subsurface};
 use crate::{hittable::HitRecord, ray::Ray, vec3::Color};
 
 // Required behaviour:
@@ -17,12 +27,14 @@ pub trait Material: Send + Sync {
     fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<ScatterRecord>;
     fn scattering_pdf(&self, ray: &Ray, rec: &HitRecord, scattered: &Ray) -> f32;
     fn emit(&self, ray: &Ray, rec: &HitRecord, u: f32, v: f32, p: &Point3) -> Color;
+    // BSDF times the cosine of the scattered direction; weights non-specular samples
+    fn eval(&self, ray: &Ray, rec: &HitRecord, scattered: &Ray) -> Color;
 }
 
 pub struct ScatterRecord<'a> {
     pub specular_ray: Option<Ray>,
     pub is_specular: bool,
-    pub attenuation: Color,
+    pub attenuation: Color, // only applied to specular rays, others are weighted by `eval`
     pub pdf: Option<PDF<'a>>,
 }
 
@@ -30,14 +42,294 @@ pub struct ScatterRecord<'a> {
 pub enum Surface<'a> {
     Lambertian(SurfaceTexture<'a>),
     Metal(Color, f32),
-    Dielectric(f32),
+    Dielectric(Dielectric<'a>),
     DiffuseLight(SurfaceTexture<'a>),
-    Isotropic(SurfaceTexture<'a>),
+    Medium(SurfaceTexture<'a>, PhaseFunction), // single-scattering albedo and phase function
+    Hair(Color, f32),                          // fiber color and longitudinal roughness
+    RoughConductor(Conductor<'a>),
+    RoughDielectric(Dielectric<'a>, f32), // interface and perceptual roughness
+    Principled(&'a Principled<'a>),
+    OrenNayar(SurfaceTexture<'a>, f32), // albedo and facet slope deviation in degrees
+    Plastic(SurfaceTexture<'a>, f32, f32), // diffuse albedo, coat refractive index and roughness
+    Bump(&'a Surface<'a>, SurfaceTexture<'a>, f32), // base surface, height texture and scale
+    NormalMap(&'a Surface<'a>, SurfaceTexture<'a>), // base surface and tangent-space normals
+    Mix(&'a Surface<'a>, &'a Surface<'a>, SurfaceTexture<'a>), // weight selects the second surface
+    Emissive(&'a Surface<'a>, Emission<'a>), // any surface, glowing
+    Subsurface(Subsurface),
 }
 
+// Smooth glass-like interface. `tint` filters light refracted into the object, while
+// `absorption` is the Beer–Lambert coefficient per unit distance travelled inside it and
+// `interior` an optional medium scattering within it. Where dielectrics overlap, the one of
+// highest `priority` fills the shared space (see `MediumStack`).
+#[derive(Copy, Clone)]
+pub struct Dielectric<'a> {
+    pub refraction_index: f32,
+    pub tint: Color,
+    pub absorption: Color,
+    pub film: Option<ThinFilm<'a>>,
+    pub interior: Option<Interior>,
+    pub priority: u32,
+    pub outside_index: f32, // of the medium around the object, set per hit by the integrator
+}
+
+impl<'a> Dielectric<'a> {
+    pub fn new(refraction_index: f32) -> Self {
+        Dielectric {
+            refraction_index,
+            tint: Color::new(1.0, 1.0, 1.0),
+            absorption: Color::new(0.0, 0.0, 0.0),
+            film: None,
+            interior: None,
+            priority: 0,
+            outside_index: 1.0,
+        }
+    }
+
+    pub fn tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    pub fn film(mut self, film: ThinFilm<'a>) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    pub fn interior(mut self, interior: Interior) -> Self {
+        self.interior = Some(interior);
+        self
+    }
+
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    // Transmitted over incident refractive index for a ray arriving on the given side
+    pub fn relative_index(&self, front_face: bool) -> f32 {
+        if front_face {
+            self.refraction_index / self.outside_index
+        } else {
+            self.outside_index / self.refraction_index
+        }
+    }
+
+    // Beer–Lambert falloff over `distance` travelled through the interior
+    pub fn transmittance(&self, distance: f32) -> Color {
+        Color::new(
+            (-self.absorption.x() * distance).exp(),
+            (-self.absorption.y() * distance).exp(),
+            (-self.absorption.z() * distance).exp(),
+        )
+    }
+}
+
+// Metal with complex refractive index `eta + ik` (per RGB channel) and GGX roughness
+#[derive(Copy, Clone)]
+pub struct Conductor<'a> {
+    pub eta: Color,
+    pub k: Color,
+    pub roughness_u: f32, // along the surface tangent
+    pub roughness_v: f32,
+    pub rotation: Option<&'a SurfaceTexture<'a>>, // of the tangent about the normal, in turns
+    pub film: Option<ThinFilm<'a>>,
+}
+
+impl<'a> Conductor<'a> {
+    pub fn new(eta: Color, k: Color, roughness: f32) -> Self {
+        Conductor {
+            eta,
+            k,
+            roughness_u: roughness,
+            roughness_v: roughness,
+            rotation: None,
+            film: None,
+        }
+    }
+
+    // Separate roughness along and across the tangent, like brushed metal
+    pub fn anisotropic(mut self, roughness_u: f32, roughness_v: f32) -> Self {
+        self.roughness_u = roughness_u;
+        self.roughness_v = roughness_v;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: &'a SurfaceTexture<'a>) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    pub fn film(mut self, film: ThinFilm<'a>) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    pub fn gold(roughness: f32) -> Self {
+        Conductor::new(Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603), roughness)
+    }
+
+    pub fn copper(roughness: f32) -> Self {
+        Conductor::new(Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142), roughness)
+    }
+
+    pub fn aluminum(roughness: f32) -> Self {
+        Conductor::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837), roughness)
+    }
+
+    pub fn distribution(&self) -> GGX {
+        GGX::new(self.roughness_u * self.roughness_u, self.roughness_v * self.roughness_v)
+    }
+
+    // Shading frame with u along the (rotated) surface tangent
+    pub fn frame(&self, rec: &HitRecord) -> ONB {
+        let (dpdu, _) = rec.tangents();
+        let angle = self.rotation.map_or(0.0, |rotation| {
+            let rotation = rotation.value(rec.u, rec.v, &rec.p);
+            2.0 * PI * (rotation.x() + rotation.y() + rotation.z()) / 3.0
+        });
+        let tangent = dpdu * angle.cos() + rec.normal.cross(&dpdu) * angle.sin();
+
+        ONB::from_normal_tangent(&rec.normal, &tangent)
+    }
+}
+
+// Light given off by a `Surface::Emissive`, as radiance of `texture` times `strength`. One-sided
+// emitters only glow from their front face.
+#[derive(Copy, Clone)]
+pub struct Emission<'a> {
+    pub texture: SurfaceTexture<'a>,
+    pub strength: f32,
+    pub two_sided: bool,
+}
+
+// Luminous efficacy of the reference 555nm light, in lumens per watt
+const LUMENS_PER_WATT: f32 = 683.0;
+
+impl<'a> Emission<'a> {
+    pub fn new(texture: SurfaceTexture<'a>) -> Self {
+        Emission { texture, strength: 1.0, two_sided: false }
+    }
+
+    // Radiance giving a luminance of `nits` (candela per square metre) for `color`; black can't
+    // reach any luminance and stays dark
+    pub fn from_nits(color: Color, nits: f32) -> Self {
+        let luminance = luminance(&color);
+        let strength = if luminance > 0.0 { nits / (LUMENS_PER_WATT * luminance) } else { 0.0 };
+
+        Emission::new(SurfaceTexture::Solid(color)).strength(strength)
+    }
+
+    // Blackbody color at `temperature` Kelvin, with a luminance of `nits`
+    pub fn from_temperature(temperature: f32, nits: f32) -> Self {
+        Emission::new(SurfaceTexture::Solid(color::blackbody(temperature, nits / LUMENS_PER_WATT)))
+    }
+
+    // Uniform radiance of a diffuse emitter of `area` radiating `watts` in total; dark, like
+    // `from_nits`, for black
+    pub fn from_watts(color: Color, watts: f32, area: f32, two_sided: bool) -> Self {
+        let sides = if two_sided { 2.0 } else { 1.0 };
+        let mean = (color.x() + color.y() + color.z()) / 3.0;
+        let strength =
+            if mean > 0.0 && area > 0.0 { watts / (sides * PI * area * mean) } else { 0.0 };
+
+        Emission::new(SurfaceTexture::Solid(color)).strength(strength).two_sided(two_sided)
+    }
+
+    pub fn strength(mut self, strength: f32) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    pub fn two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn radiance(&self, rec: &HitRecord, u: f32, v: f32, p: &Point3) -> Color {
+        if rec.front_face || self.two_sided {
+            self.texture.value(u, v, p) * self.strength
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+fn luminance(color: &Color) -> f32 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+impl<'a> Surface<'a> {
+    // The dielectric interface under any wrappers that only change its shading, if it is one.
+    // Mixes may blend in a different interface or none at all, so they don't count.
+    pub fn dielectric(&self) -> Option<&Dielectric<'a>> {
+        match self {
+            Self::Dielectric(dielectric) | Self::RoughDielectric(dielectric, _) => Some(dielectric),
+            Self::Bump(base, ..) | Self::NormalMap(base, _) | Self::Emissive(base, _) => {
+                base.dielectric()
+            }
+            _ => None,
+        }
+    }
+
+    // Whether the surface gives off light anywhere
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            Self::DiffuseLight(_) | Self::Emissive(..) => true,
+            Self::Bump(base, ..) | Self::NormalMap(base, _) => base.is_emissive(),
+            Self::Mix(first, second, _) => first.is_emissive() || second.is_emissive(),
+            _ => false,
+        }
+    }
+
+    // Copy of a dielectric surface bordering a medium of refractive index `outside`
+    pub fn surrounded_by(&self, outside: f32) -> Self {
+        match *self {
+            Self::Dielectric(dielectric) => {
+                Self::Dielectric(Dielectric { outside_index: outside, ..dielectric })
+            }
+            Self::RoughDielectric(dielectric, roughness) => Self::RoughDielectric(
+                Dielectric { outside_index: outside, ..dielectric },
+                roughness,
+            ),
+            surface => surface,
+        }
+    }
+}
+
+// Lobe selection probabilities for `Surface::Hair`; each lobe is sampled in proportion to its
+// weight, so no extra scaling of the attenuation is needed.
+const HAIR_REFLECTION: f32 = 0.25;
+const HAIR_TRANSMISSION: f32 = 0.25;
+
 impl<'a> Material for Surface<'a> {
     fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         match self {
+            Self::Bump(base, ..) | Self::NormalMap(base, _) | Self::Emissive(base, _) => {
+                base.scatter(ray, rec)
+            }
+            Self::Mix(first, second, weight) => {
+                if random_double(&mut thread_rng()) < mix_weight(weight, rec) {
+                    second.scatter(ray, rec)
+                } else {
+                    first.scatter(ray, rec)
+                }
+            }
+            Self::Subsurface(medium) => {
+                // The whole walk happens here, so the ray leaving the surface is followed like
+                // a specular one
+                medium.scatter(ray, rec).map(|(scattered, throughput)| ScatterRecord {
+                    specular_ray: Some(scattered),
+                    is_specular: true,
+                    attenuation: throughput,
+                    pdf: None,
+                })
+            }
             Self::Lambertian(albedo) => {
                 let srec = ScatterRecord {
                     specular_ray: None,
@@ -69,23 +361,43 @@ impl<'a> Material for Surface<'a> {
                     None
                 }
             }
-            Self::Dielectric(refraction_index) => {
-                // TODO: implement reflection model incorporating Fresnel reflectance
-                // Dielectric surfaces do not absorb light
-                let attenuation = Color::new(1.0, 1.0, 1.0);
-                let refraction_ratio =
-                    if rec.front_face { 1.0 / *refraction_index } else { *refraction_index };
+            Self::Dielectric(dielectric) => {
+                let refraction_ratio = 1.0 / dielectric.relative_index(rec.front_face);
 
                 let unit_direction = unit_vector(ray.direction()); // Make incident ray unit vector to simplify formula
                 let cos_theta = (-unit_direction).dot(&rec.normal).min(1.0);
-                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-                let schlick = reflectance(cos_theta, refraction_ratio);
-                let direction = if refraction_ratio * sin_theta > 1.0
-                    || schlick > random_double(&mut rand::thread_rng())
-                {
+                // Absorption inside is applied by the integrator, over each segment travelled
+                let mut attenuation = Color::new(1.0, 1.0, 1.0);
+
+                // A film colors the reflection, so choose by its mean and reweight each channel.
+                // Under total internal reflection nothing gets through, film or not.
+                let fresnel = fresnel_dielectric(cos_theta, refraction_ratio);
+                let white = Color::new(1.0, 1.0, 1.0);
+                let (reflectance, reflected, transmitted) = match dielectric.film {
+                    Some(film) if fresnel < 1.0 => {
+                        let (outside, inside) = if rec.front_face {
+                            (dielectric.outside_index, dielectric.refraction_index)
+                        } else {
+                            (dielectric.refraction_index, dielectric.outside_index)
+                        };
+                        let color = film.dielectric_reflectance(rec, cos_theta, outside, inside);
+                        let mean = clamp((color.x() + color.y() + color.z()) / 3.0, 0.0, 1.0);
+
+                        (mean, color / mean.max(1e-4), (white - color) / (1.0 - mean).max(1e-4))
+                    }
+                    _ => (fresnel, white, white),
+                };
+
+                let direction = if reflectance > random_double(&mut rand::thread_rng()) {
+                    attenuation = attenuation * reflected;
                     reflect(&unit_direction, &rec.normal)
                 } else {
+                    // Tint once per pass through the object, on the way in
+                    if rec.front_face {
+                        attenuation = attenuation * dielectric.tint;
+                    }
+                    attenuation = attenuation * transmitted;
                     refract(&unit_direction, &rec.normal, refraction_ratio)
                 };
 
@@ -98,28 +410,151 @@ impl<'a> Material for Surface<'a> {
 
                 Some(srec)
             }
-            Self::Isotropic(albedo) => {
-                // Isotropic volumes scatter light in random directions with certain probability
-                let scattered =
-                    Ray::new(rec.p, random_unit_sphere(&mut rand::thread_rng()), ray.time());
-                let attenuation = albedo.value(rec.u, rec.v, &rec.p);
-
-                // TODO
+            Self::Medium(albedo, phase) => {
                 let srec = ScatterRecord {
                     specular_ray: None,
                     is_specular: false,
+                    attenuation: albedo.value(rec.u, rec.v, &rec.p),
+                    pdf: Some(PDF::Phase(PhasePDF::new(*phase, ray.direction()))),
+                };
+
+                Some(srec)
+            }
+            Self::Hair(color, roughness) => {
+                let mut rng = rand::thread_rng();
+
+                // Fiber frame: tangent along the hair, normal and bitangent around it
+                let normal = rec.normal;
+                let tangent = if rec.dpdu.near_zero() {
+                    *vec3::coordinate_system(&normal).u()
+                } else {
+                    unit_vector(&rec.dpdu)
+                };
+                let bitangent = tangent.cross(&normal);
+
+                let wo = -unit_vector(ray.direction());
+                let sin_theta_o = clamp(wo.dot(&tangent), -1.0, 1.0);
+                let phi_o = wo.dot(&bitangent).atan2(wo.dot(&normal));
+
+                let lobe = random_double(&mut rng);
+                if lobe >= HAIR_REFLECTION + HAIR_TRANSMISSION {
+                    // Diffuse scattering from light that has bounced around inside the fiber
+                    let srec = ScatterRecord {
+                        specular_ray: None,
+                        is_specular: false,
+                        attenuation: *color,
+                        pdf: Some(PDF::Cosine(CosinePDF::new(&normal))),
+                    };
+
+                    return Some(srec);
+                }
+
+                // Specular lobes leave on the cone mirrored about the fiber's normal plane:
+                // reflection goes back towards the viewer, transmission continues through, each
+                // spread around the fiber by the roughness
+                let azimuth = PI * roughness * (random_double(&mut rng) - 0.5);
+                let (phi_i, attenuation) = if lobe < HAIR_REFLECTION {
+                    (phi_o + azimuth, Color::new(1.0, 1.0, 1.0))
+                } else {
+                    (phi_o + PI + azimuth, *color)
+                };
+                let spread = random_double(&mut rng) + random_double(&mut rng) - 1.0;
+                let theta_i = -sin_theta_o.asin() + roughness * spread;
+
+                let direction = tangent * theta_i.sin()
+                    + (normal * phi_i.cos() + bitangent * phi_i.sin()) * theta_i.cos();
+                let srec = ScatterRecord {
+                    specular_ray: Some(Ray::new(rec.p, direction, ray.time())),
+                    is_specular: true,
                     attenuation,
                     pdf: None,
                 };
 
                 Some(srec)
             }
+            Self::RoughConductor(conductor) => {
+                let wo = -*ray.direction();
+                let srec = ScatterRecord {
+                    specular_ray: None,
+                    is_specular: false,
+                    attenuation: Color::new(1.0, 1.0, 1.0),
+                    pdf: Some(PDF::Microfacet(MicrofacetPDF::oriented(
+                        conductor.frame(rec),
+                        &wo,
+                        conductor.distribution(),
+                        None,
+                    ))),
+                };
+
+                Some(srec)
+            }
+            Self::RoughDielectric(dielectric, roughness) => {
+                let wo = -*ray.direction();
+                let srec = ScatterRecord {
+                    specular_ray: None,
+                    is_specular: false,
+                    attenuation: Color::new(1.0, 1.0, 1.0),
+                    pdf: Some(PDF::Microfacet(MicrofacetPDF::new(
+                        &rec.normal,
+                        &wo,
+                        GGX::from_roughness(*roughness),
+                        Some(dielectric.relative_index(rec.front_face)),
+                    ))),
+                };
+
+                Some(srec)
+            }
+            Self::Principled(principled) => {
+                let srec = ScatterRecord {
+                    specular_ray: None,
+                    is_specular: false,
+                    attenuation: Color::new(1.0, 1.0, 1.0),
+                    pdf: Some(PDF::Principled(principled.lobes(ray, rec))),
+                };
+
+                Some(srec)
+            }
+            Self::OrenNayar(albedo, _) => {
+                let srec = ScatterRecord {
+                    specular_ray: None,
+                    is_specular: false,
+                    attenuation: albedo.value(rec.u, rec.v, &rec.p),
+                    pdf: Some(PDF::Cosine(CosinePDF::new(&rec.normal))),
+                };
+
+                Some(srec)
+            }
+            Self::Plastic(albedo, refraction_index, roughness) => {
+                let wo = -unit_vector(ray.direction());
+                let srec = ScatterRecord {
+                    specular_ray: None,
+                    is_specular: false,
+                    attenuation: Color::new(1.0, 1.0, 1.0),
+                    pdf: Some(PDF::Plastic(PlasticPDF::new(
+                        &rec.normal,
+                        &wo,
+                        GGX::from_roughness(*roughness),
+                        plastic_specular_probability(
+                            &albedo.value(rec.u, rec.v, &rec.p),
+                            *refraction_index,
+                            wo.dot(&rec.normal),
+                        ),
+                    ))),
+                };
+
+                Some(srec)
+            }
             _ => None,
         }
     }
     fn scattering_pdf(&self, ray: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
         match self {
-            Self::Lambertian(_) => {
+            Self::Bump(base, ..) | Self::NormalMap(base, _) | Self::Emissive(base, _) => {
+                base.scattering_pdf(ray, rec, scattered)
+            }
+            // Blending both children here wouldn't match `scatter`, which picks one of them
+            Self::Mix(..) => unreachable!("Mix is resolved by prepare"),
+            Self::Lambertian(_) | Self::Hair(..) | Self::OrenNayar(..) => {
                 let cosine = vec3::unit_vector(scattered.direction()).dot(&rec.normal);
                 if cosine < 0.0 {
                     0.0
@@ -127,11 +562,47 @@ impl<'a> Material for Surface<'a> {
                     cosine / PI
                 }
             }
+            Self::Medium(_, phase) => phase.value(ray.direction(), scattered.direction()),
+            Self::RoughConductor(conductor) => {
+                let frame = conductor.frame(rec);
+                let wo = frame.to_local(&-unit_vector(ray.direction()));
+                let wi = frame.to_local(&unit_vector(scattered.direction()));
+
+                microfacet::conductor_pdf(&conductor.distribution(), &wo, &wi)
+            }
+            Self::RoughDielectric(dielectric, roughness) => {
+                let frame = vec3::coordinate_system(&rec.normal);
+                let wo = frame.to_local(&-unit_vector(ray.direction()));
+                let wi = frame.to_local(&unit_vector(scattered.direction()));
+                let eta = dielectric.relative_index(rec.front_face);
+
+                microfacet::dielectric_pdf(&GGX::from_roughness(*roughness), eta, &wo, &wi)
+            }
+            Self::Principled(principled) => principled.lobes(ray, rec).pdf(scattered.direction()),
+            Self::Plastic(albedo, refraction_index, roughness) => {
+                let wo = -unit_vector(ray.direction());
+                let specular = plastic_specular_probability(
+                    &albedo.value(rec.u, rec.v, &rec.p),
+                    *refraction_index,
+                    wo.dot(&rec.normal),
+                );
+
+                PlasticPDF::new(&rec.normal, &wo, GGX::from_roughness(*roughness), specular)
+                    .value(scattered.direction())
+            }
             _ => panic!(),
         }
     }
-    fn emit(&self, ray: &Ray, rec: &HitRecord, u: f32, v: f32, p: &Point3) -> Color {
+    fn emit(&self, _ray: &Ray, rec: &HitRecord, u: f32, v: f32, p: &Point3) -> Color {
         match self {
+            Self::Bump(base, ..) | Self::NormalMap(base, _) => base.emit(_ray, rec, u, v, p),
+            Self::Emissive(base, emission) => {
+                base.emit(_ray, rec, u, v, p) + emission.radiance(rec, u, v, p)
+            }
+            Self::Mix(first, second, weight) => {
+                let w = mix_weight(weight, rec);
+                first.emit(_ray, rec, u, v, p) * (1.0 - w) + second.emit(_ray, rec, u, v, p) * w
+            }
             Self::DiffuseLight(texture) => {
                 // Only allow lights to emit light from their front surfaces.
                 if rec.front_face {
@@ -143,10 +614,316 @@ impl<'a> Material for Surface<'a> {
             _ => Vec3::new(0.0, 0.0, 0.0),
         }
     }
+    fn eval(&self, ray: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        match self {
+            Self::Bump(base, ..) | Self::NormalMap(base, _) | Self::Emissive(base, _) => {
+                base.eval(ray, rec, scattered)
+            }
+            Self::Mix(..) => unreachable!("Mix is resolved by prepare"),
+            Self::Lambertian(albedo) => {
+                albedo.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(ray, rec, scattered)
+            }
+            Self::Hair(color, _) => *color * self.scattering_pdf(ray, rec, scattered),
+            Self::Medium(albedo, phase) => {
+                albedo.value(rec.u, rec.v, &rec.p)
+                    * phase.value(ray.direction(), scattered.direction())
+            }
+            Self::RoughConductor(conductor) => {
+                let frame = conductor.frame(rec);
+                let wo = frame.to_local(&-unit_vector(ray.direction()));
+                let wi = frame.to_local(&unit_vector(scattered.direction()));
+
+                let fresnel = |cos_h: f32| match conductor.film {
+                    Some(film) => {
+                        film.conductor_reflectance(rec, cos_h, &conductor.eta, &conductor.k)
+                    }
+                    None => microfacet::fresnel_conductor(cos_h, &conductor.eta, &conductor.k),
+                };
+
+                microfacet::conductor_eval(&conductor.distribution(), fresnel, &wo, &wi)
+            }
+            Self::RoughDielectric(dielectric, roughness) => {
+                let frame = vec3::coordinate_system(&rec.normal);
+                let wo = frame.to_local(&-unit_vector(ray.direction()));
+                let wi = frame.to_local(&unit_vector(scattered.direction()));
+                let eta = dielectric.relative_index(rec.front_face);
+
+                let value =
+                    microfacet::dielectric_eval(&GGX::from_roughness(*roughness), eta, &wo, &wi);
+                let mut filter = if rec.front_face && wi.z() < 0.0 {
+                    dielectric.tint
+                } else {
+                    Color::new(1.0, 1.0, 1.0)
+                };
+
+                // A film colors the reflection off each microfacet, so trade the plain Fresnel
+                // reflectance in the value for the film's
+                let half = microfacet::dielectric_half_vector(eta, &wo, &wi);
+                if let (Some(film), Some((wm, reflect))) = (dielectric.film, half) {
+                    let cos_h = wo.dot(&wm);
+                    let (outside, inside) = if rec.front_face {
+                        (dielectric.outside_index, dielectric.refraction_index)
+                    } else {
+                        (dielectric.refraction_index, dielectric.outside_index)
+                    };
+                    let color = film.dielectric_reflectance(rec, cos_h, outside, inside);
+                    let fresnel = fresnel_dielectric(cos_h, 1.0 / eta);
+                    let white = Color::new(1.0, 1.0, 1.0);
+
+                    filter = filter
+                        * if reflect {
+                            color / fresnel.max(1e-4)
+                        } else {
+                            (white - color) / (1.0 - fresnel).max(1e-4)
+                        };
+                }
+
+                filter * value
+            }
+            Self::Principled(principled) => principled.lobes(ray, rec).eval(scattered.direction()),
+            Self::OrenNayar(albedo, sigma) => {
+                let wo = -unit_vector(ray.direction());
+                let wi = unit_vector(scattered.direction());
+
+                albedo.value(rec.u, rec.v, &rec.p) * oren_nayar(*sigma, &rec.normal, &wo, &wi)
+            }
+            Self::Plastic(albedo, refraction_index, roughness) => {
+                let frame = vec3::coordinate_system(&rec.normal);
+                let wo = frame.to_local(&-unit_vector(ray.direction()));
+                let wi = frame.to_local(&unit_vector(scattered.direction()));
+                if wo.z() <= 0.0 || wi.z() <= 0.0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+
+                // Fresnel-weighted reflection off the coat
+                let coat = microfacet::dielectric_eval(
+                    &GGX::from_roughness(*roughness),
+                    *refraction_index,
+                    &wo,
+                    &wi,
+                );
+
+                // Light refracted into the coat, scattered by the base and refracted back out,
+                // with repeated internal reflections between the two folded into the albedo
+                let fresnel_in = fresnel_dielectric(wi.z(), 1.0 / refraction_index);
+                let fresnel_out = fresnel_dielectric(wo.z(), 1.0 / refraction_index);
+                let albedo =
+                    plastic_base_albedo(&albedo.value(rec.u, rec.v, &rec.p), *refraction_index);
+                let diffuse = albedo
+                    * ((1.0 - fresnel_in) * (1.0 - fresnel_out) * wi.z()
+                        / (PI * refraction_index * refraction_index));
+
+                diffuse + Color::new(coat, coat, coat)
+            }
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
 }
 
-fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
-    let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-    let r0 = r0 * r0;
-    r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
+// Resolves wrappers around the hit surface: bump and normal maps replace the shading normal, and
+// mixes pick one child at random in proportion to their weights. Hands back the record with the
+// underlying material; called once per hit, after emission and before any scattering.
+pub fn prepare<'a>(rec: &HitRecord<'a>) -> HitRecord<'a> {
+    let mut rec = *rec;
+
+    loop {
+        let (normal, base) = match rec.material {
+            Surface::Bump(base, height, scale) => (bump_normal(&rec, height, *scale), *base),
+            Surface::NormalMap(base, map) => (mapped_normal(&rec, map), *base),
+            Surface::Emissive(base, _) => {
+                rec.material = base;
+                continue;
+            }
+            Surface::Mix(first, second, weight) => {
+                rec.material = if random_double(&mut thread_rng()) < mix_weight(weight, &rec) {
+                    second
+                } else {
+                    first
+                };
+                continue;
+            }
+            _ => return rec,
+        };
+
+        // Perturbations that tip the normal below the true surface would leak light through it
+        if normal.dot(&rec.geometric_normal) > 0.0 {
+            let (dpdu, _) = rec.tangents();
+            rec.normal = normal;
+            rec.dpdu = dpdu - normal * dpdu.dot(&normal);
+        }
+        rec.material = base;
+    }
+}
+
+// Share of the second surface in a `Surface::Mix`
+fn mix_weight(weight: &SurfaceTexture, rec: &HitRecord) -> f32 {
+    weight.alpha(rec.u, rec.v, &rec.p)
+}
+
+// Shading normal of the surface offset along its normal by the height texture, from finite
+// differences of the height in u and v
+fn bump_normal(rec: &HitRecord, height: &SurfaceTexture, scale: f32) -> Vec3 {
+    const DELTA: f32 = 0.0005;
+
+    let (dpdu, dpdv) = rec.tangents();
+    let displacement = |u: f32, v: f32, p: &Point3| {
+        let value = height.value(u, v, p);
+        scale * (value.x() + value.y() + value.z()) / 3.0
+    };
+
+    let center = displacement(rec.u, rec.v, &rec.p);
+    let du = (displacement(rec.u + DELTA, rec.v, &(rec.p + dpdu * DELTA)) - center) / DELTA;
+    let dv = (displacement(rec.u, rec.v + DELTA, &(rec.p + dpdv * DELTA)) - center) / DELTA;
+
+    // Heights are measured along the outward normal, whichever side the ray came from
+    let outward = if rec.front_face { rec.normal } else { -rec.normal };
+    let normal = unit_vector(&(dpdu + outward * du).cross(&(dpdv + outward * dv)));
+
+    if normal.dot(&rec.normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+// Shading normal read from a tangent-space normal map, with tangent along dp/du and the
+// bitangent on the side of dp/dv
+fn mapped_normal(rec: &HitRecord, map: &SurfaceTexture) -> Vec3 {
+    let (dpdu, dpdv) = rec.tangents();
+    let color = map.value(rec.u, rec.v, &rec.p);
+    let local = color * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+
+    let tangent = unit_vector(&(dpdu - rec.normal * dpdu.dot(&rec.normal)));
+    let bitangent = rec.normal.cross(&tangent);
+    let bitangent = if bitangent.dot(&dpdv) < 0.0 { -bitangent } else { bitangent };
+
+    unit_vector(&(tangent * local.x() + bitangent * local.y() + rec.normal * local.z()))
+}
+
+// Oren–Nayar reflectance (without albedo) times the cosine of `wi`, for facet slopes with
+// standard deviation `sigma` in degrees
+fn oren_nayar(sigma: f32, normal: &Vec3, wo: &Vec3, wi: &Vec3) -> f32 {
+    let cos_i = wi.dot(normal);
+    let cos_o = wo.dot(normal);
+    if cos_i <= 0.0 || cos_o <= 0.0 {
+        return 0.0;
+    }
+
+    let sigma = degrees_to_radians(sigma);
+    let sigma2 = sigma * sigma;
+    let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+    let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+    let sin_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+    let sin_o = (1.0 - cos_o * cos_o).max(0.0).sqrt();
+
+    // Cosine of the azimuthal angle between the two directions
+    let projected_i = *wi - *normal * cos_i;
+    let projected_o = *wo - *normal * cos_o;
+    let cos_phi = if sin_i > 1e-4 && sin_o > 1e-4 {
+        (projected_i.dot(&projected_o) / (sin_i * sin_o)).max(0.0)
+    } else {
+        0.0
+    };
+
+    let (sin_alpha, tan_beta) =
+        if cos_i > cos_o { (sin_o, sin_i / cos_i) } else { (sin_i, sin_o / cos_o) };
+
+    (a + b * cos_phi * sin_alpha * tan_beta) * cos_i / PI
+}
+
+// Albedo of a plastic's base seen through its coat: each channel bounces between the base and
+// the underside of the coat, reflecting `internal` of what the base sends up each time
+fn plastic_base_albedo(albedo: &Color, refraction_index: f32) -> Color {
+    let internal = microfacet::fresnel_diffuse_reflectance(1.0 / refraction_index);
+    let white = Color::new(1.0, 1.0, 1.0);
+
+    *albedo / (white - *albedo * internal)
+}
+
+// How often a plastic samples its coat rather than its base: the coat's Fresnel reflectance
+// towards the viewer against what the base can send back
+fn plastic_specular_probability(albedo: &Color, refraction_index: f32, cos_o: f32) -> f32 {
+    let fresnel = fresnel_dielectric(cos_o, 1.0 / refraction_index);
+    let internal = microfacet::fresnel_diffuse_reflectance(1.0 / refraction_index);
+    let base = plastic_base_albedo(albedo, refraction_index) * (1.0 - internal);
+    let diffuse = (1.0 - fresnel) * (base.x() + base.y() + base.z()) / 3.0;
+
+    // Neither lobe reflects anything, as for a black base under a coat matching the outside
+    if fresnel + diffuse <= 0.0 {
+        return 0.0;
+    }
+
+    fresnel / (fresnel + diffuse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Integral of `eval` over the upper hemisphere around +z, by the midpoint rule
+    fn albedo(surface: &Surface, wo: &Vec3) -> Color {
+        let rec = HitRecord::new(
+            Point3::default(),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            0.5,
+            0.5,
+            true,
+            surface,
+        );
+        let ray = Ray::new(*wo, -*wo, 0.0);
+
+        let (steps_theta, steps_phi) = (400, 400);
+        let (d_theta, d_phi) = (0.5 * PI / steps_theta as f32, 2.0 * PI / steps_phi as f32);
+        let mut total = Color::default();
+        for i in 0..steps_theta {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let wi = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                let scattered = Ray::new(Point3::default(), wi, 0.0);
+                total += surface.eval(&ray, &rec, &scattered) * (theta.sin() * d_theta * d_phi);
+            }
+        }
+
+        total
+    }
+
+    #[test]
+    fn plastic_base_reflects_each_channel_through_the_coat() {
+        // A white, a grey and a black channel under the same coat; the black one shows only the
+        // coat's own reflection
+        let base = SurfaceTexture::Solid(Color::new(1.0, 0.5, 0.0));
+        let plastic = Surface::Plastic(base, 1.5, 0.3);
+        let wo = vec3::unit_vector(&Vec3::new(0.4, 0.1, 1.0));
+        let albedo = albedo(&plastic, &wo);
+
+        let outside = 1.0 - fresnel_dielectric(wo.z(), 1.0 / 1.5);
+        let internal = microfacet::fresnel_diffuse_reflectance(1.0 / 1.5);
+        let grey = outside * (1.0 - internal) * 0.5 / (1.0 - 0.5 * internal);
+
+        // Everything a white base sends back up eventually leaves through the coat
+        assert!((albedo.x() - albedo.z() - outside).abs() < 0.01, "white {:?}", albedo);
+        assert!((albedo.y() - albedo.z() - grey).abs() < 0.01, "grey {:?}", albedo);
+    }
+
+    #[test]
+    fn filmed_glass_reflects_totally_inside() {
+        let glass = Surface::Dielectric(Dielectric::new(1.5).film(ThinFilm::new(300.0, 1.33)));
+        // Hit from inside, with the normal turned against the ray
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+        let rec = HitRecord::new(Point3::default(), normal, 1.0, 0.5, 0.5, false, &glass);
+
+        // Leaving glass at 60 degrees, past the critical angle of about 42
+        let direction = Vec3::new(60f32.to_radians().sin(), 0.0, 60f32.to_radians().cos());
+        let ray = Ray::new(-direction, direction, 0.0);
+        for _ in 0..100 {
+            let srec = glass.scatter(&ray, &rec).unwrap();
+            let scattered = srec.specular_ray.unwrap();
+
+            assert!(scattered.direction().z() < 0.0);
+            assert_eq!(vec3::unpack(&srec.attenuation), [1.0, 1.0, 1.0]);
+        }
+    }
 }
@@ -0,0 +1,201 @@
+use crate::material::{Dielectric, Surface};
+
+// Deepest nesting tracked; entering more dielectrics than this ignores the innermost
+const MAX_NESTING: usize = 8;
+
+// Dielectrics a ray is inside, in the order it entered them, each identified by the surface it
+// was hit on. Where they overlap, the one of highest priority fills the shared space, and on ties
+// the latest entered: a liquid nested inside a solid glass ball of the same priority fills the
+// core, while an ice cube of higher priority keeps its shape where it dips into the water around
+// it. Surfaces hidden inside a higher-priority medium aren't interfaces at all. Only surfaces
+// `Surface::dielectric` sees through are tracked, so mixed glass refracts but isn't entered.
+#[derive(Copy, Clone, Default)]
+pub struct MediumStack<'a> {
+    entries: [Option<&'a Surface<'a>>; MAX_NESTING],
+    len: usize,
+}
+
+impl<'a> MediumStack<'a> {
+    pub fn new() -> Self {
+        MediumStack { entries: [None; MAX_NESTING], len: 0 }
+    }
+
+    // Medium filling the space the ray is travelling through, if it isn't in the open
+    pub fn current(&self) -> Option<&'a Dielectric<'a>> {
+        self.highest(None)
+    }
+
+    // Whether a hit on `surface` changes medium, rather than being hidden inside one of higher
+    // priority that doesn't end there
+    pub fn is_interface(&self, surface: &Surface) -> bool {
+        match (surface.dielectric(), self.highest(Some(surface))) {
+            (Some(dielectric), Some(other)) => other.priority <= dielectric.priority,
+            _ => true,
+        }
+    }
+
+    // Refractive index on the other side of `surface` from its own interior
+    pub fn outside_index(&self, surface: &Surface) -> f32 {
+        self.highest(Some(surface)).map_or(1.0, |dielectric| dielectric.refraction_index)
+    }
+
+    // Media after passing through `surface`, entering it if the hit was on its front face. Only
+    // dielectrics bound a medium; other surfaces, and collisions inside volumes, change nothing.
+    pub fn cross(&self, surface: &'a Surface<'a>, front_face: bool) -> Self {
+        let mut stack = *self;
+
+        if surface.dielectric().is_none() {
+            return stack;
+        }
+
+        if front_face {
+            if stack.len < MAX_NESTING {
+                stack.entries[stack.len] = Some(surface);
+                stack.len += 1;
+            }
+        } else if let Some(index) = stack.entries[..stack.len]
+            .iter()
+            .rposition(|entry| entry.is_some_and(|entry| std::ptr::eq(entry, surface)))
+        {
+            stack.entries.copy_within(index + 1..stack.len, index);
+            stack.len -= 1;
+            stack.entries[stack.len] = None;
+        }
+
+        stack
+    }
+
+    // Highest-priority medium entered, latest first on ties, passing over `excluding`
+    fn highest(&self, excluding: Option<&Surface>) -> Option<&'a Dielectric<'a>> {
+        let mut highest: Option<&'a Dielectric<'a>> = None;
+
+        for entry in self.entries[..self.len].iter().flatten() {
+            if excluding.is_some_and(|surface| std::ptr::eq(*entry, surface)) {
+                continue;
+            }
+
+            if let Some(dielectric) = entry.dielectric() {
+                if highest.is_none_or(|current| dielectric.priority >= current.priority) {
+                    highest = Some(dielectric);
+                }
+            }
+        }
+
+        highest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{phase::PhaseFunction::Isotropic, texture::SurfaceTexture, vec3::Color};
+
+    fn index(medium: Option<&Dielectric>) -> Option<f32> {
+        medium.map(|dielectric| dielectric.refraction_index)
+    }
+
+    #[test]
+    fn open_space() {
+        let glass = Surface::Dielectric(Dielectric::new(1.5));
+        let stack = MediumStack::new();
+
+        assert!(stack.current().is_none());
+        assert!(stack.is_interface(&glass));
+        assert_eq!(stack.outside_index(&glass), 1.0);
+    }
+
+    #[test]
+    fn nested_media_of_equal_priority() {
+        let glass = Surface::Dielectric(Dielectric::new(1.5));
+        let wine = Surface::Dielectric(Dielectric::new(1.33));
+
+        let in_glass = MediumStack::new().cross(&glass, true);
+        assert_eq!(index(in_glass.current()), Some(1.5));
+
+        // The latest entered fills the space, and refracts against the one around it
+        let in_wine = in_glass.cross(&wine, true);
+        assert!(in_wine.is_interface(&wine));
+        assert_eq!(index(in_wine.current()), Some(1.33));
+        assert_eq!(in_wine.outside_index(&wine), 1.5);
+
+        let back_in_glass = in_wine.cross(&wine, false);
+        assert_eq!(index(back_in_glass.current()), Some(1.5));
+        assert!(back_in_glass.cross(&glass, false).current().is_none());
+    }
+
+    #[test]
+    fn higher_priority_hides_surfaces_inside_it() {
+        let water = Surface::Dielectric(Dielectric::new(1.33));
+        let ice = Surface::Dielectric(Dielectric::new(1.31).priority(1));
+
+        // Water entered after the ice, where they overlap, is still ice
+        let stack = MediumStack::new().cross(&ice, true);
+        assert!(!stack.is_interface(&water));
+        let stack = stack.cross(&water, true);
+        assert_eq!(index(stack.current()), Some(1.31));
+
+        // Leaving the ice into the water refracts against the water
+        assert!(stack.is_interface(&ice));
+        assert_eq!(stack.outside_index(&ice), 1.33);
+        assert_eq!(index(stack.cross(&ice, false).current()), Some(1.33));
+    }
+
+    #[test]
+    fn leaving_out_of_order() {
+        let first = Surface::Dielectric(Dielectric::new(1.5));
+        let second = Surface::Dielectric(Dielectric::new(1.4));
+
+        let stack =
+            MediumStack::new().cross(&first, true).cross(&second, true).cross(&first, false);
+        assert_eq!(index(stack.current()), Some(1.4));
+        assert_eq!(stack.outside_index(&second), 1.0);
+    }
+
+    #[test]
+    fn leaving_what_was_never_entered_changes_nothing() {
+        let glass = Surface::Dielectric(Dielectric::new(1.5));
+        let other = Surface::Dielectric(Dielectric::new(1.5));
+
+        let stack = MediumStack::new().cross(&glass, true).cross(&other, false);
+        assert!(std::ptr::eq(stack.current().unwrap(), glass.dielectric().unwrap()));
+        assert!(MediumStack::new().cross(&other, false).current().is_none());
+    }
+
+    #[test]
+    fn other_surfaces_are_always_interfaces() {
+        let glass = Surface::Dielectric(Dielectric::new(1.5).priority(3));
+        let diffuse = Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.5, 0.5, 0.5)));
+
+        let stack = MediumStack::new().cross(&glass, true);
+        assert!(stack.is_interface(&diffuse));
+    }
+
+    #[test]
+    fn crossing_other_surfaces_changes_nothing() {
+        let glass = Surface::Dielectric(Dielectric::new(1.5));
+        let diffuse = Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.5, 0.5, 0.5)));
+        let smoke = Surface::Medium(SurfaceTexture::Solid(Color::new(0.5, 0.5, 0.5)), Isotropic);
+
+        // As often as volume collisions and diffuse transmission would, filling up the stack
+        let stack = (0..2 * MAX_NESTING)
+            .fold(MediumStack::new(), |stack, _| stack.cross(&diffuse, true).cross(&smoke, true));
+        assert!(stack.current().is_none());
+
+        let stack = stack.cross(&glass, true).cross(&diffuse, true).cross(&smoke, true);
+        assert_eq!(index(stack.current()), Some(1.5));
+        assert!(stack.cross(&glass, false).current().is_none());
+    }
+
+    #[test]
+    fn nesting_past_the_limit_is_ignored() {
+        let shells: Vec<Surface> = (0..MAX_NESTING + 2)
+            .map(|i| Surface::Dielectric(Dielectric::new(1.1 + 0.1 * i as f32)))
+            .collect();
+
+        let stack = shells.iter().fold(MediumStack::new(), |stack, shell| stack.cross(shell, true));
+        assert_eq!(
+            index(stack.current()),
+            Some(shells[MAX_NESTING - 1].dielectric().unwrap().refraction_index)
+        );
+    }
+}
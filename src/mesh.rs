@@ -0,0 +1,505 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{
+    aabb::AABB,
+    bvh::IndexedBVH,
+    hittable::{HitRecord, Hittable},
+    material::Surface,
+    ray::Ray,
+    sphere::sphere_uv,
+    texture::{SurfaceTexture, Texture},
+    utility::random_double,
+    vec3::{self, Point3, Vec3},
+};
+
+// Most splits `Mesh::tessellate` makes; each one quadruples the triangle count
+const MAX_TESSELLATION_LEVELS: u32 = 8;
+
+// Polygonal mesh description, e.g. a subdivision control cage. Faces may have any number of
+// vertices; `creases` tags edges (as vertex index pairs) with a sharpness, where
+// `f32::INFINITY` marks an edge that stays sharp at every subdivision level.
+#[derive(Clone)]
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub uvs: Option<Vec<(f32, f32)>>, // per-vertex texture coordinates
+    pub faces: Vec<Vec<usize>>,
+    pub creases: Vec<(usize, usize, f32)>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Point3>, faces: Vec<Vec<usize>>) -> Self {
+        Mesh { positions, uvs: None, faces, creases: Vec::new() }
+    }
+
+    pub fn add_crease(&mut self, a: usize, b: usize, sharpness: f32) {
+        self.creases.push((a, b, sharpness));
+    }
+
+    pub fn is_triangle_mesh(&self) -> bool {
+        self.faces.iter().all(|face| face.len() == 3)
+    }
+
+    // Fan triangulation of every face
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        self.faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |i| [face[0], face[i], face[i + 1]]))
+            .collect()
+    }
+
+    // Area-weighted vertex normals (the unnormalized cross product carries the area)
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for [a, b, c] in self.triangles() {
+            let p = &self.positions;
+            let face_normal = (p[b] - p[a]).cross(&(p[c] - p[a]));
+            normals[a] += face_normal;
+            normals[b] += face_normal;
+            normals[c] += face_normal;
+        }
+
+        normals
+            .into_iter()
+            .map(|n| if n.near_zero() { Vec3::new(0.0, 1.0, 0.0) } else { vec3::unit_vector(&n) })
+            .collect()
+    }
+
+    // Triangulates, then splits every triangle into four until no edge is longer than
+    // `max_edge_length`. Refinement is uniform so neighbouring triangles always agree on their
+    // shared edges and displacement can't open cracks. As the count grows by four times per split,
+    // refinement stops after `MAX_TESSELLATION_LEVELS` splits (65536 triangles per input
+    // triangle) even if some edges are still too long.
+    pub fn tessellate(&self, max_edge_length: f32) -> Mesh {
+        assert!(max_edge_length > 0.0, "Edge length must be positive");
+
+        let mut mesh = Mesh {
+            positions: self.positions.clone(),
+            uvs: self.uvs.clone(),
+            faces: self.triangles().iter().map(|t| t.to_vec()).collect(),
+            creases: Vec::new(),
+        };
+
+        for _ in 0..MAX_TESSELLATION_LEVELS {
+            if mesh.longest_edge() <= max_edge_length {
+                break;
+            }
+            mesh = mesh.split_triangles();
+        }
+
+        mesh
+    }
+
+    // Offsets every vertex along its smooth normal by `scale` times the mean channel value of
+    // `texture`, looked up at the vertex's uv and position.
+    pub fn displace(&self, texture: &SurfaceTexture, scale: f32) -> Mesh {
+        let normals = self.vertex_normals();
+        let positions = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let (u, v) = self.uvs.as_ref().map_or((0.0, 0.0), |uvs| uvs[i]);
+                let value = texture.value(u, v, p);
+                let height = (value.x() + value.y() + value.z()) / 3.0;
+
+                *p + normals[i] * (height * scale)
+            })
+            .collect();
+
+        Mesh { positions, ..self.clone() }
+    }
+
+    fn longest_edge(&self) -> f32 {
+        self.triangles()
+            .iter()
+            .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+            .map(|(a, b)| (self.positions[a] - self.positions[b]).length())
+            .fold(0.0, f32::max)
+    }
+
+    // Midpoint split of each triangle into four, sharing the new vertex on each edge
+    fn split_triangles(&self) -> Mesh {
+        let mut positions = self.positions.clone();
+        let mut uvs = self.uvs.clone();
+        let mut midpoints = HashMap::new();
+
+        let mut midpoint = |a: usize, b: usize| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push((positions[a] + positions[b]) * 0.5);
+                if let Some(uvs) = uvs.as_mut() {
+                    uvs.push(((uvs[a].0 + uvs[b].0) * 0.5, (uvs[a].1 + uvs[b].1) * 0.5));
+                }
+                positions.len() - 1
+            })
+        };
+
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        for face in self.faces.iter() {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+
+            faces.push(vec![a, ab, ca]);
+            faces.push(vec![b, bc, ab]);
+            faces.push(vec![c, ca, bc]);
+            faces.push(vec![ab, bc, ca]);
+        }
+
+        Mesh { positions, uvs, faces, creases: Vec::new() }
+    }
+
+    // Axis-aligned cube of quads spanning p0 to p1, with per-vertex uvs from a spherical
+    // projection about its center.
+    pub fn cube(p0: Point3, p1: Point3) -> Self {
+        let positions: Vec<Point3> = (0..8)
+            .map(|i| {
+                Point3::new(
+                    if i & 1 == 0 { p0.x() } else { p1.x() },
+                    if i & 2 == 0 { p0.y() } else { p1.y() },
+                    if i & 4 == 0 { p0.z() } else { p1.z() },
+                )
+            })
+            .collect();
+
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+
+        let center = (p0 + p1) * 0.5;
+        let uvs = positions.iter().map(|p| sphere_uv(&vec3::unit_vector(&(*p - center)))).collect();
+
+        Mesh { positions, uvs: Some(uvs), faces, creases: Vec::new() }
+    }
+
+    // Minimal Wavefront OBJ reader: vertex positions, texture coordinates and faces. Texture
+    // coordinates are stored per vertex, so seams take whichever value was referenced last.
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut positions = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut uvs: Vec<Option<(f32, f32)>> = Vec::new();
+        let mut faces = Vec::new();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", line_number + 1, message),
+                )
+            };
+            let parse = |token: Option<&str>| {
+                token.and_then(|t| t.parse::<f32>().ok()).ok_or_else(|| invalid("malformed number"))
+            };
+
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    positions.push(Point3::new(
+                        parse(tokens.next())?,
+                        parse(tokens.next())?,
+                        parse(tokens.next())?,
+                    ));
+                    uvs.push(None);
+                }
+                Some("vt") => texcoords.push((parse(tokens.next())?, parse(tokens.next())?)),
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for corner in tokens {
+                        let mut indices = corner.split('/');
+                        let vertex = resolve_index(indices.next(), positions.len())
+                            .ok_or_else(|| invalid("invalid vertex index"))?;
+
+                        if let Some(texcoord) = resolve_index(indices.next(), texcoords.len()) {
+                            uvs[vertex] = Some(texcoords[texcoord]);
+                        }
+                        face.push(vertex);
+                    }
+
+                    if face.len() < 3 {
+                        return Err(invalid("face with fewer than three vertices"));
+                    }
+                    faces.push(face);
+                }
+                _ => (),
+            }
+        }
+
+        let uvs = if uvs.iter().all(|uv| uv.is_some()) && !uvs.is_empty() {
+            Some(uvs.into_iter().map(|uv| uv.unwrap()).collect())
+        } else {
+            None
+        };
+
+        Ok(Mesh { positions, uvs, faces, creases: Vec::new() })
+    }
+}
+
+// Renderable triangle mesh with smooth per-vertex normals and a BVH over its triangles.
+#[derive(Clone)]
+pub struct TriangleMesh<'a> {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f32, f32)>,
+    triangles: Vec<[usize; 3]>,
+    // Running total of triangle areas, for picking triangles to sample as a light
+    areas: Vec<f32>,
+    bvh: IndexedBVH,
+    material: Surface<'a>,
+}
+
+impl<'a> TriangleMesh<'a> {
+    pub fn new(mesh: &Mesh, material: Surface<'a>) -> Self {
+        let positions = mesh.positions.clone();
+        let triangles = mesh.triangles();
+
+        let normals = mesh.vertex_normals();
+
+        let uvs = match &mesh.uvs {
+            Some(uvs) => uvs.clone(),
+            None => vec![(0.0, 0.0); positions.len()],
+        };
+
+        let boxes: Vec<AABB> = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                let (p0, p1, p2) = (positions[*a], positions[*b], positions[*c]);
+                let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+                AABB::new(
+                    Point3::new(
+                        p0.x().min(p1.x()).min(p2.x()),
+                        p0.y().min(p1.y()).min(p2.y()),
+                        p0.z().min(p1.z()).min(p2.z()),
+                    ) - pad,
+                    Point3::new(
+                        p0.x().max(p1.x()).max(p2.x()),
+                        p0.y().max(p1.y()).max(p2.y()),
+                        p0.z().max(p1.z()).max(p2.z()),
+                    ) + pad,
+                )
+            })
+            .collect();
+        let bvh = IndexedBVH::new(&boxes);
+
+        let areas = triangles
+            .iter()
+            .scan(0.0, |total, [a, b, c]| {
+                let (p0, p1, p2) = (positions[*a], positions[*b], positions[*c]);
+                *total += 0.5 * (p1 - p0).cross(&(p2 - p0)).length();
+                Some(*total)
+            })
+            .collect();
+
+        TriangleMesh { positions, normals, uvs, triangles, areas, bvh, material }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn hit_triangle(&self, index: usize, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let [a, b, c] = self.triangles[index];
+        let (p0, p1, p2) = (self.positions[a], self.positions[b], self.positions[c]);
+        let (t, b1, b2) = hit_triangle(r, &p0, &p1, &p2, tmin, tmax)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let p = r.at(t);
+        let normal = vec3::unit_vector(
+            &(self.normals[a] * b0 + self.normals[b] * b1 + self.normals[c] * b2),
+        );
+        let u = self.uvs[a].0 * b0 + self.uvs[b].0 * b1 + self.uvs[c].0 * b2;
+        let v = self.uvs[a].1 * b0 + self.uvs[b].1 * b1 + self.uvs[c].1 * b2;
+
+        // Sidedness comes from the flat triangle, shading from the interpolated normal
+        let geometric_normal = vec3::unit_vector(&(p1 - p0).cross(&(p2 - p0)));
+        let front_face = HitRecord::face_normal(r, &geometric_normal);
+
+        let mut rec = if front_face {
+            HitRecord::new(p, normal, t, u, v, front_face, &self.material)
+        } else {
+            HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+        };
+        rec.geometric_normal = if front_face { geometric_normal } else { -geometric_normal };
+
+        // Solve for the position derivatives from the edge vectors and their uv differences
+        let (duv02, duv12) = (
+            (self.uvs[a].0 - self.uvs[c].0, self.uvs[a].1 - self.uvs[c].1),
+            (self.uvs[b].0 - self.uvs[c].0, self.uvs[b].1 - self.uvs[c].1),
+        );
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+        let determinant = duv02.0 * duv12.1 - duv02.1 * duv12.0;
+        if determinant.abs() > 1e-8 {
+            rec.dpdu = (dp02 * duv12.1 - dp12 * duv02.1) / determinant;
+            rec.dpdv = (dp12 * duv02.0 - dp02 * duv12.0) / determinant;
+        } else {
+            let frame = vec3::coordinate_system(&geometric_normal);
+            rec.dpdu = *frame.u();
+            rec.dpdv = *frame.v();
+        }
+
+        Some(rec)
+    }
+}
+
+impl Hittable for TriangleMesh<'_> {
+    fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, tmin, tmax, |i, tmin, tmax| self.hit_triangle(i, r, tmin, tmax))
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> Option<AABB> {
+        self.bvh.bounding_box()
+    }
+
+    // Sums over every triangle along `v`, since a sample on one can be hidden behind another
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        let area = self.areas.last().copied().unwrap_or(0.0);
+        if area <= 0.0 {
+            return 0.0;
+        }
+
+        let r = Ray::new(*origin, *v, 0.0);
+        let mut pdf = 0.0;
+        let mut tmin = 0.001;
+        while let Some(hit_rec) = self.hit(&r, tmin, f32::INFINITY) {
+            let dist_squared = hit_rec.t * hit_rec.t * v.length_squared();
+            let cosine = (v.dot(&hit_rec.geometric_normal) / v.length()).abs();
+            if cosine > 0.0 {
+                pdf += dist_squared / (cosine * area);
+            }
+            tmin = hit_rec.t + 0.0001;
+        }
+
+        pdf
+    }
+
+    // Uniform point over the surface: a triangle in proportion to its area, then a point on it
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let area = self.areas.last().copied().unwrap_or(0.0);
+        let target = random_double(&mut rng) * area;
+        let index = self.areas.partition_point(|total| *total <= target).min(self.areas.len() - 1);
+
+        let [a, b, c] = self.triangles[index];
+        let (p0, p1, p2) = (self.positions[a], self.positions[b], self.positions[c]);
+        let r1 = random_double(&mut rng).sqrt();
+        let r2 = random_double(&mut rng);
+        let point = p0 * (1.0 - r1) + p1 * (r1 * (1.0 - r2)) + p2 * (r1 * r2);
+
+        point - *origin
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive() && !self.triangles.is_empty()
+    }
+}
+
+// Möller–Trumbore intersection, returning the ray parameter and the barycentrics of p1 and p2
+pub fn hit_triangle(
+    r: &Ray,
+    p0: &Point3,
+    p1: &Point3,
+    p2: &Point3,
+    tmin: f32,
+    tmax: f32,
+) -> Option<(f32, f32, f32)> {
+    let e1 = *p1 - *p0;
+    let e2 = *p2 - *p0;
+    let pvec = r.direction().cross(&e2);
+    let det = e1.dot(&pvec);
+
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = *r.origin() - *p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&e1);
+    let b2 = r.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = e2.dot(&qvec) * inv_det;
+    if t < tmin || t > tmax {
+        None
+    } else {
+        Some((t, b1, b2))
+    }
+}
+
+// OBJ indices are 1-based, or negative to count back from the most recent element
+fn resolve_index(token: Option<&str>, count: usize) -> Option<usize> {
+    let index = token.filter(|t| !t.is_empty())?.parse::<i64>().ok()?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+
+    if resolved >= 0 && (resolved as usize) < count {
+        Some(resolved as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utility::random_unit_vector, vec3::Color};
+
+    fn glowing_cube() -> TriangleMesh<'static> {
+        let cube = Mesh::cube(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        TriangleMesh::new(
+            &cube,
+            Surface::DiffuseLight(SurfaceTexture::Solid(Color::new(1.0, 1.0, 1.0))),
+        )
+    }
+
+    #[test]
+    fn light_samples_land_on_the_mesh() {
+        let light = glowing_cube();
+        assert!(light.is_light());
+
+        let origin = Point3::new(0.5, 3.0, -2.0);
+        for _ in 0..1000 {
+            let direction = light.random(&origin);
+            let hit = light.hit(&Ray::new(origin, direction, 0.0), 0.001, f32::INFINITY);
+            assert!(hit.is_some_and(|rec| rec.t <= 1.0 + 1e-3));
+        }
+    }
+
+    #[test]
+    fn light_pdf_integrates_to_one() {
+        let light = glowing_cube();
+        let origin = Point3::new(0.5, 3.0, -2.0);
+        let mut rng = rand::thread_rng();
+
+        let samples = 200_000;
+        let total: f64 = (0..samples)
+            .map(|_| light.pdf_value(&origin, &random_unit_vector(&mut rng)) as f64)
+            .sum();
+        let integral = total * 4.0 * std::f64::consts::PI / samples as f64;
+
+        assert!((integral - 1.0).abs() < 0.05, "integral {}", integral);
+    }
+
+    #[test]
+    fn tessellation_stops_at_the_level_limit() {
+        let triangle = Mesh::new(
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            vec![vec![0, 1, 2]],
+        );
+
+        assert_eq!(triangle.tessellate(0.4).faces.len(), 16);
+        assert_eq!(triangle.tessellate(1e-6).faces.len(), 4usize.pow(MAX_TESSELLATION_LEVELS));
+    }
+}
@@ -0,0 +1,398 @@
+use std::f32::consts::PI;
+
+use crate::{
+    utility::clamp,
+    vec3::{self, Color, Vec3},
+};
+
+// GGX (Trowbridge–Reitz) distribution of microfacet normals. All directions are in a local
+// shading frame with the macro-surface normal along +z.
+#[derive(Copy, Clone)]
+pub struct GGX {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl GGX {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Self {
+        // Keep clear of a delta distribution, which can't be evaluated
+        GGX { alpha_x: alpha_x.max(0.001), alpha_y: alpha_y.max(0.001) }
+    }
+
+    // Isotropic distribution from perceptual roughness in [0, 1]
+    pub fn from_roughness(roughness: f32) -> Self {
+        let alpha = roughness * roughness;
+        GGX::new(alpha, alpha)
+    }
+
+    pub fn d(&self, wm: &Vec3) -> f32 {
+        let x = wm.x() / self.alpha_x;
+        let y = wm.y() / self.alpha_y;
+        let denominator = x * x + y * y + wm.z() * wm.z();
+
+        1.0 / (PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    fn lambda(&self, w: &Vec3) -> f32 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f32::INFINITY;
+        }
+
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        0.5 * (-1.0 + (1.0 + (x * x + y * y) / cos2).sqrt())
+    }
+
+    pub fn g1(&self, w: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking-shadowing
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the visible normals from `wo`, with respect to solid angle of `wm`
+    pub fn pdf(&self, wo: &Vec3, wm: &Vec3) -> f32 {
+        if wo.z() == 0.0 {
+            return 0.0;
+        }
+
+        self.g1(wo) * self.d(wm) * wo.dot(wm).max(0.0) / wo.z().abs()
+    }
+
+    // Samples a microfacet normal visible from `wo` (Heitz 2018)
+    pub fn sample_wm(&self, wo: &Vec3, u1: f32, u2: f32) -> Vec3 {
+        // Stretch the view direction to the hemisphere configuration
+        let mut wh =
+            vec3::unit_vector(&Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()));
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+
+        let length_squared = wh.x() * wh.x() + wh.y() * wh.y();
+        let t1 = if length_squared > 0.0 {
+            Vec3::new(-wh.y(), wh.x(), 0.0) / length_squared.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(&t1);
+
+        // Uniform disk sample, warped onto the visible half of the projected hemisphere
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + wh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let pz = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        let nh = t1 * p1 + t2 * p2 + wh * pz;
+
+        vec3::unit_vector(&Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        ))
+    }
+}
+
+// Exact Fresnel reflectance for unpolarized light, averaging the s- and p-polarized terms.
+// `ratio` is the incident over the transmitted refractive index; returns 1 past the critical
+// angle (total internal reflection).
+pub fn fresnel_dielectric(cos_i: f32, ratio: f32) -> f32 {
+    let cos_i = clamp(cos_i, 0.0, 1.0);
+    let sin_t2 = ratio * ratio * (1.0 - cos_i * cos_i);
+    if sin_t2 >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin_t2).sqrt();
+    let r_s = (ratio * cos_i - cos_t) / (ratio * cos_i + cos_t);
+    let r_p = (ratio * cos_t - cos_i) / (ratio * cos_t + cos_i);
+
+    0.5 * (r_s * r_s + r_p * r_p)
+}
+
+// Hemispherical average of `fresnel_dielectric` for light arriving from the side with relative
+// index `eta` (transmitted over incident), using the fit of Egan and Hilgeman below 1 and that of
+// d'Eon and Irving in `1 / eta` above it
+pub fn fresnel_diffuse_reflectance(eta: f32) -> f32 {
+    if eta < 1.0 {
+        -1.4399 * eta * eta + 0.7099 * eta + 0.6681 + 0.0636 / eta
+    } else {
+        let inv = 1.0 / eta;
+        0.919317 - 3.4793 * inv + 6.75335 * inv.powi(2) - 7.80989 * inv.powi(3)
+            + 4.98554 * inv.powi(4)
+            - 1.36881 * inv.powi(5)
+    }
+}
+
+// Fresnel reflectance of a conductor with complex refractive index `eta + ik`, per channel
+pub fn fresnel_conductor(cos_i: f32, eta: &Color, k: &Color) -> Color {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = clamp(cos_i * cos_i, 0.0, 1.0);
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i.abs() * a;
+        let r_s = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+        0.5 * (r_s + r_p)
+    };
+
+    Color::new(channel(eta.x(), k.x()), channel(eta.y(), k.y()), channel(eta.z(), k.z()))
+}
+
+// Rough conductor BSDF times the cosine of `wi`, with `fresnel` giving the reflectance for the
+// cosine between `wo` and the microfacet normal
+pub fn conductor_eval(
+    distribution: &GGX,
+    fresnel: impl Fn(f32) -> Color,
+    wo: &Vec3,
+    wi: &Vec3,
+) -> Color {
+    if wo.z() <= 0.0 || wi.z() <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let wm = *wo + *wi;
+    if wm.near_zero() {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let wm = vec3::unit_vector(&wm);
+
+    fresnel(wo.dot(&wm)) * distribution.d(&wm) * distribution.g(wo, wi) / (4.0 * wo.z())
+}
+
+pub fn conductor_pdf(distribution: &GGX, wo: &Vec3, wi: &Vec3) -> f32 {
+    if wo.z() <= 0.0 || wi.z() <= 0.0 {
+        return 0.0;
+    }
+
+    let wm = *wo + *wi;
+    if wm.near_zero() {
+        return 0.0;
+    }
+    let wm = vec3::unit_vector(&wm);
+
+    distribution.pdf(wo, &wm) / (4.0 * wo.dot(&wm))
+}
+
+pub fn conductor_sample(distribution: &GGX, wo: &Vec3, u1: f32, u2: f32) -> Vec3 {
+    let wm = distribution.sample_wm(wo, u1, u2);
+    vec3::reflect(&-*wo, &wm)
+}
+
+// Half vector of a rough dielectric interaction, oriented to +z. `eta` is the transmitted over
+// the incident refractive index. Returns `None` for configurations no microfacet can produce.
+pub fn dielectric_half_vector(eta: f32, wo: &Vec3, wi: &Vec3) -> Option<(Vec3, bool)> {
+    let reflect = wi.z() > 0.0;
+    let wm = if reflect { *wo + *wi } else { *wi * eta + *wo };
+    if wm.near_zero() || wo.z() == 0.0 || wi.z() == 0.0 {
+        return None;
+    }
+
+    let wm = vec3::unit_vector(&wm);
+    let wm = if wm.z() < 0.0 { -wm } else { wm };
+
+    // Discard back-facing microfacets
+    if wm.dot(wi) * wi.z() < 0.0 || wm.dot(wo) * wo.z() < 0.0 {
+        return None;
+    }
+
+    Some((wm, reflect))
+}
+
+// Rough dielectric BSDF times the cosine of `wi` (Walter et al. 2007), with `wo` above the
+// surface and `eta` the transmitted over the incident refractive index
+pub fn dielectric_eval(distribution: &GGX, eta: f32, wo: &Vec3, wi: &Vec3) -> f32 {
+    let (wm, reflect) = match dielectric_half_vector(eta, wo, wi) {
+        Some(half) => half,
+        None => return 0.0,
+    };
+
+    let fresnel = fresnel_dielectric(wo.dot(&wm), 1.0 / eta);
+    if reflect {
+        distribution.d(&wm) * distribution.g(wo, wi) * fresnel / (4.0 * wo.z().abs())
+    } else {
+        let denominator = wi.dot(&wm) + wo.dot(&wm) / eta;
+        distribution.d(&wm)
+            * distribution.g(wo, wi)
+            * (1.0 - fresnel)
+            * wi.dot(&wm).abs()
+            * wo.dot(&wm).abs()
+            / (wo.z().abs() * denominator * denominator)
+    }
+}
+
+pub fn dielectric_pdf(distribution: &GGX, eta: f32, wo: &Vec3, wi: &Vec3) -> f32 {
+    let (wm, reflect) = match dielectric_half_vector(eta, wo, wi) {
+        Some(half) => half,
+        None => return 0.0,
+    };
+
+    let fresnel = fresnel_dielectric(wo.dot(&wm), 1.0 / eta);
+    if reflect {
+        fresnel * distribution.pdf(wo, &wm) / (4.0 * wo.dot(&wm).abs())
+    } else {
+        let denominator = wi.dot(&wm) + wo.dot(&wm) / eta;
+        (1.0 - fresnel) * distribution.pdf(wo, &wm) * wi.dot(&wm).abs()
+            / (denominator * denominator)
+    }
+}
+
+// Picks reflection or refraction through a sampled microfacet in proportion to its Fresnel
+// reflectance
+pub fn dielectric_sample(distribution: &GGX, eta: f32, wo: &Vec3, u: [f32; 3]) -> Vec3 {
+    let wm = distribution.sample_wm(wo, u[0], u[1]);
+    let fresnel = fresnel_dielectric(wo.dot(&wm), 1.0 / eta);
+
+    if u[2] < fresnel {
+        vec3::reflect(&-*wo, &wm)
+    } else {
+        vec3::refract(&-*wo, &wm, 1.0 / eta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    #[test]
+    fn fresnel_dielectric_at_normal_incidence() {
+        // ((n - 1) / (n + 1))^2 for glass, seen from either side
+        assert!(close(fresnel_dielectric(1.0, 1.0 / 1.5), 0.04, 1e-4));
+        assert!(close(fresnel_dielectric(1.0, 1.5), 0.04, 1e-4));
+    }
+
+    #[test]
+    fn fresnel_dielectric_total_internal_reflection() {
+        // Critical angle leaving glass is asin(1 / 1.5), about 41.8 degrees
+        let cos_i = 40f32.to_radians().cos();
+        assert!(fresnel_dielectric(cos_i, 1.5) < 1.0);
+        let cos_i = 43f32.to_radians().cos();
+        assert_eq!(fresnel_dielectric(cos_i, 1.5), 1.0);
+    }
+
+    #[test]
+    fn fresnel_diffuse_reflectance_of_glass() {
+        // Internal reflectance from inside glass, then external from outside
+        assert!(close(fresnel_diffuse_reflectance(1.0 / 1.5), 0.597, 2e-3));
+        assert!(close(fresnel_diffuse_reflectance(1.5), 0.092, 2e-3));
+    }
+
+    #[test]
+    fn fresnel_diffuse_reflectance_matches_integral() {
+        // 2 * integral of F(cos) * cos over the hemisphere, by the midpoint rule
+        for eta in [1.0 / 1.8, 1.0 / 1.33, 1.33, 1.8] {
+            let steps = 20000;
+            let integral: f32 = (0..steps)
+                .map(|i| {
+                    let cos_i = (i as f32 + 0.5) / steps as f32;
+                    2.0 * fresnel_dielectric(cos_i, 1.0 / eta) * cos_i / steps as f32
+                })
+                .sum();
+
+            assert!(close(fresnel_diffuse_reflectance(eta), integral, 5e-3));
+        }
+    }
+
+    // Integral of `f` over the whole sphere of directions, by the midpoint rule in spherical
+    // coordinates
+    fn integrate_sphere(f: impl Fn(&Vec3) -> f32) -> f32 {
+        let (steps_theta, steps_phi) = (800, 400);
+        let (d_theta, d_phi) = (PI / steps_theta as f32, 2.0 * PI / steps_phi as f32);
+
+        let mut total = 0.0f64;
+        for i in 0..steps_theta {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let w = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                total += (f(&w) * theta.sin() * d_theta * d_phi) as f64;
+            }
+        }
+
+        total as f32
+    }
+
+    fn viewers() -> [Vec3; 3] {
+        [
+            Vec3::new(0.0, 0.0, 1.0),
+            vec3::unit_vector(&Vec3::new(0.5, 0.2, 0.8)),
+            vec3::unit_vector(&Vec3::new(-0.9, 0.3, 0.25)),
+        ]
+    }
+
+    #[test]
+    fn visible_normal_pdf_integrates_to_one() {
+        for distribution in [GGX::new(0.3, 0.3), GGX::new(0.6, 0.6), GGX::new(0.2, 0.5)] {
+            for wo in viewers() {
+                let integral =
+                    integrate_sphere(
+                        |wm| {
+                            if wm.z() > 0.0 {
+                                distribution.pdf(&wo, wm)
+                            } else {
+                                0.0
+                            }
+                        },
+                    );
+                assert!(close(integral, 1.0, 0.01), "integral {}", integral);
+            }
+        }
+    }
+
+    #[test]
+    fn conductor_pdf_loses_only_reflections_below_the_horizon() {
+        for distribution in [GGX::new(0.3, 0.3), GGX::new(0.6, 0.6)] {
+            for wo in viewers() {
+                let integral = integrate_sphere(|wi| conductor_pdf(&distribution, &wo, wi));
+                assert!(integral <= 1.005, "integral {}", integral);
+            }
+        }
+
+        // Seen head on, normals tilted past 45 degrees reflect below the horizon, and GGX puts a
+        // fraction alpha^2 / (1 + alpha^2) of them there
+        let wo = Vec3::new(0.0, 0.0, 1.0);
+        for alpha in [0.3f32, 0.6] {
+            let distribution = GGX::new(alpha, alpha);
+            let integral = integrate_sphere(|wi| conductor_pdf(&distribution, &wo, wi));
+            assert!(close(integral, 1.0 / (1.0 + alpha * alpha), 0.01), "integral {}", integral);
+        }
+    }
+
+    #[test]
+    fn dielectric_pdf_integrates_to_at_most_one() {
+        // Reflection and transmission together, entering and leaving glass
+        let distribution = GGX::new(0.4, 0.4);
+        for eta in [1.5, 1.0 / 1.5] {
+            for wo in viewers() {
+                let integral = integrate_sphere(|wi| dielectric_pdf(&distribution, eta, &wo, wi));
+                assert!(integral <= 1.005, "integral {}", integral);
+            }
+        }
+
+        // Entering, only the faint reflections off steep normals are lost
+        let wo = Vec3::new(0.0, 0.0, 1.0);
+        let integral = integrate_sphere(|wi| dielectric_pdf(&distribution, 1.5, &wo, wi));
+        assert!(integral > 0.97, "integral {}", integral);
+
+        // Leaving head on, normals past 45 degrees reflect totally and below the horizon
+        let integral = integrate_sphere(|wi| dielectric_pdf(&distribution, 1.0 / 1.5, &wo, wi));
+        assert!(close(integral, 1.0 / (1.0 + 0.4 * 0.4), 0.01), "integral {}", integral);
+    }
+}
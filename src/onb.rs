@@ -16,6 +16,21 @@ impl ONB {
         ONB { uvw: [u, v, w] }
     }
 
+    // Basis with `w` along the normal and `u` along the tangent, projected onto the plane
+    // perpendicular to it. Falls back to an arbitrary `u` when the tangent is along the normal.
+    pub fn from_normal_tangent(normal: &Vec3, tangent: &Vec3) -> Self {
+        let w = vec3::unit_vector(normal);
+        let u = *tangent - w * tangent.dot(&w);
+        if u.near_zero() {
+            return ONB::new(&w);
+        }
+
+        let u = vec3::unit_vector(&u);
+        let v = w.cross(&u);
+
+        ONB { uvw: [u, v, w] }
+    }
+
     pub fn u(&self) -> &Vec3 {
         &self.uvw[0]
     }
@@ -35,6 +50,11 @@ impl ONB {
     pub fn local_vec(&self, v: &Vec3) -> Vec3 {
         *self.u() * v.x() + *self.v() * v.y() + *self.w() * v.z()
     }
+
+    // Inverse of `local_vec`: expresses a world-space vector in this basis
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.u()), v.dot(self.v()), v.dot(self.w()))
+    }
 }
 
 impl Index<usize> for ONB {
@@ -4,7 +4,10 @@ use rand::{prelude::ThreadRng, thread_rng};
 
 use crate::{
     hittable::{HitModel, Hittable, HittableList},
+    microfacet::{self, GGX},
     onb::ONB,
+    phase::PhaseFunction,
+    principled::Lobes,
     utility::{random_double, random_in_hemisphere},
     vec3::{self, Point3, Vec3},
 };
@@ -28,6 +31,10 @@ pub enum PDF<'a> {
     Uniform(UniformPDF),
     Cosine(CosinePDF),
     Hittable(HittablePDF<'a>),
+    Microfacet(MicrofacetPDF),
+    Principled(Lobes),
+    Plastic(PlasticPDF),
+    Phase(PhasePDF),
 }
 
 impl PDF<'_> {
@@ -36,6 +43,10 @@ impl PDF<'_> {
             Self::Uniform(p) => p.value(direction),
             Self::Cosine(p) => p.value(direction),
             Self::Hittable(p) => p.value(direction),
+            Self::Microfacet(p) => p.value(direction),
+            Self::Principled(lobes) => lobes.pdf(direction),
+            Self::Plastic(p) => p.value(direction),
+            Self::Phase(p) => p.value(direction),
         }
     }
 
@@ -44,6 +55,10 @@ impl PDF<'_> {
             Self::Uniform(p) => p.generate(),
             Self::Cosine(p) => p.generate(),
             Self::Hittable(p) => p.generate(),
+            Self::Microfacet(p) => p.generate(),
+            Self::Principled(lobes) => lobes.sample(),
+            Self::Plastic(p) => p.generate(),
+            Self::Phase(p) => p.generate(),
         }
     }
 }
@@ -108,6 +123,99 @@ impl<'a> HittablePDF<'a> {
     }
 }
 
+// Visible-normal sampling of a rough conductor, or of a rough dielectric when `eta` (transmitted
+// over incident refractive index) is given. `wo` points away from the surface.
+pub struct MicrofacetPDF {
+    frame: ONB,
+    wo: Vec3,
+    distribution: GGX,
+    eta: Option<f32>,
+}
+
+impl MicrofacetPDF {
+    pub fn new(normal: &Vec3, wo: &Vec3, distribution: GGX, eta: Option<f32>) -> Self {
+        MicrofacetPDF::oriented(vec3::coordinate_system(normal), wo, distribution, eta)
+    }
+
+    // For anisotropic distributions, whose x and y axes follow the frame's u and v
+    pub fn oriented(frame: ONB, wo: &Vec3, distribution: GGX, eta: Option<f32>) -> Self {
+        let wo = frame.to_local(&vec3::unit_vector(wo));
+
+        MicrofacetPDF { frame, wo, distribution, eta }
+    }
+
+    pub fn value(&self, direction: &Vec3) -> f32 {
+        let wi = self.frame.to_local(&vec3::unit_vector(direction));
+
+        match self.eta {
+            Some(eta) => microfacet::dielectric_pdf(&self.distribution, eta, &self.wo, &wi),
+            None => microfacet::conductor_pdf(&self.distribution, &self.wo, &wi),
+        }
+    }
+
+    pub fn generate(&self) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let u = [random_double(&mut rng), random_double(&mut rng), random_double(&mut rng)];
+
+        let wi = match self.eta {
+            Some(eta) => microfacet::dielectric_sample(&self.distribution, eta, &self.wo, u),
+            None => microfacet::conductor_sample(&self.distribution, &self.wo, u[0], u[1]),
+        };
+        self.frame.local_vec(&wi)
+    }
+}
+
+// Coated diffuse surface: samples reflection off the coat with probability `specular`,
+// otherwise the diffuse base underneath
+pub struct PlasticPDF {
+    diffuse: CosinePDF,
+    coat: MicrofacetPDF,
+    specular: f32,
+}
+
+impl PlasticPDF {
+    pub fn new(normal: &Vec3, wo: &Vec3, distribution: GGX, specular: f32) -> Self {
+        PlasticPDF {
+            diffuse: CosinePDF::new(normal),
+            coat: MicrofacetPDF::new(normal, wo, distribution, None),
+            specular,
+        }
+    }
+
+    pub fn value(&self, direction: &Vec3) -> f32 {
+        self.specular * self.coat.value(direction)
+            + (1.0 - self.specular) * self.diffuse.value(direction)
+    }
+
+    pub fn generate(&self) -> Vec3 {
+        if random_double(&mut rand::thread_rng()) < self.specular {
+            self.coat.generate()
+        } else {
+            self.diffuse.generate()
+        }
+    }
+}
+
+// Scattering inside a medium, about the direction the light was travelling
+pub struct PhasePDF {
+    phase: PhaseFunction,
+    direction: Vec3,
+}
+
+impl PhasePDF {
+    pub fn new(phase: PhaseFunction, direction: &Vec3) -> Self {
+        PhasePDF { phase, direction: vec3::unit_vector(direction) }
+    }
+
+    pub fn value(&self, direction: &Vec3) -> f32 {
+        self.phase.value(&self.direction, direction)
+    }
+
+    pub fn generate(&self) -> Vec3 {
+        self.phase.sample(&self.direction)
+    }
+}
+
 pub struct MixturePDF<'a> {
     p: [PDF<'a>; 2],
 }
@@ -0,0 +1,74 @@
+use crate::{
+    utility::{random_double, PI},
+    vec3::{self, Vec3},
+};
+
+// Angular distribution of light scattered in a participating medium. Every phase function is
+// normalized over the sphere of directions, and `value` and `sample` take the direction the
+// light was travelling before scattering.
+#[derive(Copy, Clone)]
+pub enum PhaseFunction {
+    Isotropic,
+    HenyeyGreenstein(f32), // asymmetry g in (-1, 1); positive scatters forward
+    DoubleHenyeyGreenstein(f32, f32, f32), // two asymmetries, and the weight of the first lobe
+    Rayleigh,              // scattering by particles much smaller than the wavelength
+}
+
+impl PhaseFunction {
+    pub fn value(&self, direction: &Vec3, scattered: &Vec3) -> f32 {
+        let cos_theta = vec3::unit_vector(direction).dot(&vec3::unit_vector(scattered));
+
+        match self {
+            Self::Isotropic => 1.0 / (4.0 * PI),
+            Self::HenyeyGreenstein(g) => henyey_greenstein(*g, cos_theta),
+            Self::DoubleHenyeyGreenstein(g1, g2, weight) => {
+                weight * henyey_greenstein(*g1, cos_theta)
+                    + (1.0 - weight) * henyey_greenstein(*g2, cos_theta)
+            }
+            Self::Rayleigh => 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta),
+        }
+    }
+
+    // Draws a scattered direction in proportion to `value`
+    pub fn sample(&self, direction: &Vec3) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let u = random_double(&mut rng);
+
+        let cos_theta = match self {
+            Self::Isotropic => 1.0 - 2.0 * u,
+            Self::HenyeyGreenstein(g) => sample_henyey_greenstein(*g, u),
+            Self::DoubleHenyeyGreenstein(g1, g2, weight) => {
+                let g = if random_double(&mut rng) < *weight { g1 } else { g2 };
+                sample_henyey_greenstein(*g, u)
+            }
+            Self::Rayleigh => {
+                // Closed-form inverse of the cubic CDF
+                let a = 4.0 * u - 2.0;
+                let s = (a * a + 1.0).sqrt();
+                (a + s).cbrt() + (a - s).cbrt()
+            }
+        };
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double(&mut rng);
+        vec3::coordinate_system(direction).local(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        )
+    }
+}
+
+fn henyey_greenstein(g: f32, cos_theta: f32) -> f32 {
+    let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+}
+
+fn sample_henyey_greenstein(g: f32, u: f32) -> f32 {
+    if g.abs() < 1e-3 {
+        return 1.0 - 2.0 * u;
+    }
+
+    let ratio = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+    (1.0 + g * g - ratio * ratio) / (2.0 * g)
+}
@@ -0,0 +1,235 @@
+use std::f32::consts::PI;
+
+use crate::{
+    hittable::HitRecord,
+    microfacet::{self, GGX},
+    onb::ONB,
+    pdf::random_cosine_direction,
+    ray::Ray,
+    texture::{SurfaceTexture, Texture},
+    utility::{clamp, random_double},
+    vec3::{self, Color, Vec3},
+};
+
+// Disney-style "principled" uber-material. Every parameter is a texture; scalar parameters
+// read the mean of the texture's channels. Too large to copy around, so `Surface::Principled`
+// borrows it from the caller.
+#[derive(Copy, Clone)]
+pub struct Principled<'a> {
+    pub base_color: SurfaceTexture<'a>,
+    pub metallic: SurfaceTexture<'a>,
+    pub roughness: SurfaceTexture<'a>,
+    pub specular: SurfaceTexture<'a>,
+    pub specular_tint: SurfaceTexture<'a>,
+    pub sheen: SurfaceTexture<'a>,
+    pub clearcoat: SurfaceTexture<'a>,
+    pub transmission: SurfaceTexture<'a>,
+    pub ior: SurfaceTexture<'a>,
+}
+
+// Fixed parameters the artist-facing set leaves out
+const SHEEN_TINT: f32 = 0.5;
+const CLEARCOAT_ROUGHNESS: f32 = 0.1;
+
+impl<'a> Principled<'a> {
+    pub fn new(base_color: SurfaceTexture<'a>) -> Self {
+        Principled {
+            base_color,
+            metallic: SurfaceTexture::constant(0.0),
+            roughness: SurfaceTexture::constant(0.5),
+            specular: SurfaceTexture::constant(0.5),
+            specular_tint: SurfaceTexture::constant(0.0),
+            sheen: SurfaceTexture::constant(0.0),
+            clearcoat: SurfaceTexture::constant(0.0),
+            transmission: SurfaceTexture::constant(0.0),
+            ior: SurfaceTexture::constant(1.45),
+        }
+    }
+
+    pub fn metallic(mut self, metallic: SurfaceTexture<'a>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn roughness(mut self, roughness: SurfaceTexture<'a>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn specular(mut self, specular: SurfaceTexture<'a>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn specular_tint(mut self, specular_tint: SurfaceTexture<'a>) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    pub fn sheen(mut self, sheen: SurfaceTexture<'a>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn clearcoat(mut self, clearcoat: SurfaceTexture<'a>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn transmission(mut self, transmission: SurfaceTexture<'a>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn ior(mut self, ior: SurfaceTexture<'a>) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    // Looks up every parameter at the hit point and sets up the lobes in the local frame
+    pub fn lobes(&self, ray: &Ray, rec: &HitRecord) -> Lobes {
+        let scalar = |texture: &SurfaceTexture| {
+            let value = texture.value(rec.u, rec.v, &rec.p);
+            (value.x() + value.y() + value.z()) / 3.0
+        };
+
+        let base_color = self.base_color.value(rec.u, rec.v, &rec.p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let transmission = scalar(&self.transmission);
+        let ior = scalar(&self.ior);
+
+        // Hue and saturation of the base color, at unit luminance
+        let luminance = 0.2126 * base_color.x() + 0.7152 * base_color.y() + 0.0722 * base_color.z();
+        let tint = if luminance > 0.0 { base_color / luminance } else { Color::new(1.0, 1.0, 1.0) };
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        let dielectric_f0 = (white + (tint - white) * scalar(&self.specular_tint))
+            * (0.08 * scalar(&self.specular));
+        let sheen_color = (white + (tint - white) * SHEEN_TINT) * scalar(&self.sheen);
+
+        let weights = [
+            (1.0 - metallic) * (1.0 - transmission),
+            1.0 - transmission * (1.0 - metallic),
+            0.25 * scalar(&self.clearcoat),
+            transmission * (1.0 - metallic),
+        ];
+        let total: f32 = weights.iter().sum();
+        let mut probabilities = [0.0; 4];
+        for (p, w) in probabilities.iter_mut().zip(weights.iter()) {
+            *p = w / total;
+        }
+
+        let frame = vec3::coordinate_system(&rec.normal);
+        let wo = frame.to_local(&-vec3::unit_vector(ray.direction()));
+
+        Lobes {
+            frame,
+            wo,
+            base_color,
+            roughness,
+            specular_f0: dielectric_f0 + (base_color - dielectric_f0) * metallic,
+            sheen_color,
+            specular: GGX::from_roughness(roughness),
+            clearcoat: GGX::from_roughness(CLEARCOAT_ROUGHNESS),
+            eta: if rec.front_face { ior } else { 1.0 / ior },
+            weights,
+            probabilities,
+        }
+    }
+}
+
+// A principled material resolved at one hit point: diffuse (with sheen), specular reflection,
+// clearcoat and rough transmission lobes, sampled in proportion to their weights.
+pub struct Lobes {
+    frame: ONB,
+    wo: Vec3,
+    base_color: Color,
+    roughness: f32,
+    specular_f0: Color,
+    sheen_color: Color,
+    specular: GGX,
+    clearcoat: GGX,
+    eta: f32, // transmitted over incident refractive index
+    weights: [f32; 4],
+    probabilities: [f32; 4],
+}
+
+impl Lobes {
+    // BSDF times the cosine of `direction`
+    pub fn eval(&self, direction: &Vec3) -> Color {
+        let wo = self.wo;
+        let wi = self.frame.to_local(&vec3::unit_vector(direction));
+        let [diffuse_weight, specular_weight, clearcoat_weight, transmission_weight] = self.weights;
+
+        let mut value = self.base_color
+            * (transmission_weight
+                * microfacet::dielectric_eval(&self.specular, self.eta, &wo, &wi));
+
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return value;
+        }
+
+        let wh = wo + wi;
+        if wh.near_zero() {
+            return value;
+        }
+        let wh = vec3::unit_vector(&wh);
+        let cos_d = wi.dot(&wh);
+
+        // Burley diffuse with grazing retro-reflection, plus sheen
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fl = schlick_weight(wi.z());
+        let fv = schlick_weight(wo.z());
+        let diffuse = self.base_color / PI * (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv)
+            + self.sheen_color * schlick_weight(cos_d);
+        value += diffuse * (diffuse_weight * wi.z());
+
+        let fh = schlick_weight(wo.dot(&wh));
+        let fresnel = self.specular_f0 + (Color::new(1.0, 1.0, 1.0) - self.specular_f0) * fh;
+        value += fresnel * (specular_weight * self.specular.d(&wh) * self.specular.g(&wo, &wi))
+            / (4.0 * wo.z());
+
+        let coat_fresnel = 0.04 + 0.96 * fh;
+        let coat =
+            clearcoat_weight * coat_fresnel * self.clearcoat.d(&wh) * self.clearcoat.g(&wo, &wi)
+                / (4.0 * wo.z());
+        value + Color::new(coat, coat, coat)
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        let wi = self.frame.to_local(&vec3::unit_vector(direction));
+        let [diffuse, specular, clearcoat, transmission] = self.probabilities;
+
+        let cosine = if wi.z() > 0.0 { wi.z() / PI } else { 0.0 };
+        diffuse * cosine
+            + specular * microfacet::conductor_pdf(&self.specular, &self.wo, &wi)
+            + clearcoat * microfacet::conductor_pdf(&self.clearcoat, &self.wo, &wi)
+            + transmission * microfacet::dielectric_pdf(&self.specular, self.eta, &self.wo, &wi)
+    }
+
+    pub fn sample(&self) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let [diffuse, specular, clearcoat, _] = self.probabilities;
+        let lobe = random_double(&mut rng);
+        let u = [random_double(&mut rng), random_double(&mut rng), random_double(&mut rng)];
+
+        let wi = if lobe < diffuse {
+            random_cosine_direction(&mut rng)
+        } else if lobe < diffuse + specular {
+            microfacet::conductor_sample(&self.specular, &self.wo, u[0], u[1])
+        } else if lobe < diffuse + specular + clearcoat {
+            microfacet::conductor_sample(&self.clearcoat, &self.wo, u[0], u[1])
+        } else {
+            microfacet::dielectric_sample(&self.specular, self.eta, &self.wo, u)
+        };
+
+        self.frame.local_vec(&wi)
+    }
+}
+
+fn schlick_weight(cosine: f32) -> f32 {
+    let m = clamp(1.0 - cosine, 0.0, 1.0);
+    let m2 = m * m;
+    m2 * m2 * m
+}
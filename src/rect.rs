@@ -30,7 +30,8 @@ impl Hittable for XYRect<'_> {
         let XYRect { x0, x1, y0, y1, k, .. } = *self;
         let t = (k - r.origin().z()) / r.direction().z();
 
-        if t < tmin || t > tmax {
+        // Rays parallel to the plane give no t, or NaN when starting in it
+        if t.is_nan() || t < tmin || t > tmax {
             None
         } else {
             let x = r.origin().x() + t * r.direction().x();
@@ -45,11 +46,15 @@ impl Hittable for XYRect<'_> {
                 let normal = Vec3::new(0.0, 0.0, 1.0);
                 let front_face = HitRecord::face_normal(r, &normal);
 
-                if front_face {
-                    Some(HitRecord::new(p, normal, t, u, v, front_face, &self.material))
+                let mut rec = if front_face {
+                    HitRecord::new(p, normal, t, u, v, front_face, &self.material)
                 } else {
-                    Some(HitRecord::new(p, -normal, t, u, v, front_face, &self.material))
-                }
+                    HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+                };
+                rec.dpdu = Vec3::new(x1 - x0, 0.0, 0.0);
+                rec.dpdv = Vec3::new(0.0, y1 - y0, 0.0);
+
+                Some(rec)
             }
         }
     }
@@ -60,6 +65,33 @@ impl Hittable for XYRect<'_> {
             Vec3::new(self.x1, self.y1, self.k + 0.0001),
         ))
     }
+
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        if let Some(hit_rec) = self.hit(&Ray::new(*origin, *v, 0.0), 0.001, f32::INFINITY) {
+            let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+            let dist_squared = hit_rec.t * hit_rec.t * v.length_squared();
+            let cosine = (v.dot(&hit_rec.normal) / v.length()).abs();
+
+            dist_squared / (cosine * area)
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let random_point = Point3::new(
+            random_double_range(&mut rng, self.x0, self.x1),
+            random_double_range(&mut rng, self.y0, self.y1),
+            self.k,
+        );
+
+        random_point - *origin
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -85,7 +117,8 @@ impl Hittable for XZRect<'_> {
         let XZRect { x0, x1, z0, z1, k, .. } = *self;
         let t = (k - r.origin().y()) / r.direction().y();
 
-        if t < tmin || t > tmax {
+        // Rays parallel to the plane give no t, or NaN when starting in it
+        if t.is_nan() || t < tmin || t > tmax {
             None
         } else {
             let x = r.origin().x() + t * r.direction().x();
@@ -100,11 +133,15 @@ impl Hittable for XZRect<'_> {
                 let normal = Vec3::new(0.0, 1.0, 0.0);
                 let front_face = HitRecord::face_normal(r, &normal);
 
-                if front_face {
-                    Some(HitRecord::new(p, normal, t, u, v, front_face, &self.material))
+                let mut rec = if front_face {
+                    HitRecord::new(p, normal, t, u, v, front_face, &self.material)
                 } else {
-                    Some(HitRecord::new(p, -normal, t, u, v, front_face, &self.material))
-                }
+                    HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+                };
+                rec.dpdu = Vec3::new(x1 - x0, 0.0, 0.0);
+                rec.dpdv = Vec3::new(0.0, 0.0, z1 - z0);
+
+                Some(rec)
             }
         }
     }
@@ -112,7 +149,7 @@ impl Hittable for XZRect<'_> {
     fn bounding_box(&self, _: f32, _: f32) -> Option<AABB> {
         Some(AABB::new(
             Vec3::new(self.x0, self.k - 0.0001, self.z0),
-            Vec3::new(self.x0, self.k + 0.0001, self.z1),
+            Vec3::new(self.x1, self.k + 0.0001, self.z1),
         ))
     }
 
@@ -138,6 +175,10 @@ impl Hittable for XZRect<'_> {
 
         random_point - *origin
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -163,7 +204,8 @@ impl Hittable for YZRect<'_> {
         let YZRect { y0, y1, z0, z1, k, .. } = *self;
         let t = (k - r.origin().x()) / r.direction().x();
 
-        if t < tmin || t > tmax {
+        // Rays parallel to the plane give no t, or NaN when starting in it
+        if t.is_nan() || t < tmin || t > tmax {
             None
         } else {
             let y = r.origin().y() + t * r.direction().y();
@@ -178,11 +220,15 @@ impl Hittable for YZRect<'_> {
                 let normal = Vec3::new(1.0, 0.0, 0.0);
                 let front_face = HitRecord::face_normal(r, &normal);
 
-                if front_face {
-                    Some(HitRecord::new(p, normal, t, u, v, front_face, &self.material))
+                let mut rec = if front_face {
+                    HitRecord::new(p, normal, t, u, v, front_face, &self.material)
                 } else {
-                    Some(HitRecord::new(p, -normal, t, u, v, front_face, &self.material))
-                }
+                    HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+                };
+                rec.dpdu = Vec3::new(0.0, y1 - y0, 0.0);
+                rec.dpdv = Vec3::new(0.0, 0.0, z1 - z0);
+
+                Some(rec)
             }
         }
     }
@@ -193,6 +239,33 @@ impl Hittable for YZRect<'_> {
             Vec3::new(self.k + 0.0001, self.y1, self.z1),
         ))
     }
+
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        if let Some(hit_rec) = self.hit(&Ray::new(*origin, *v, 0.0), 0.001, f32::INFINITY) {
+            let area = (self.y1 - self.y0) * (self.z1 - self.z0);
+            let dist_squared = hit_rec.t * hit_rec.t * v.length_squared();
+            let cosine = (v.dot(&hit_rec.normal) / v.length()).abs();
+
+            dist_squared / (cosine * area)
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let random_point = Point3::new(
+            self.k,
+            random_double_range(&mut rng, self.y0, self.y1),
+            random_double_range(&mut rng, self.z0, self.z1),
+        );
+
+        random_point - *origin
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
 }
 
 #[derive(Clone)]
@@ -229,4 +302,17 @@ impl Hittable for Box<'_> {
     fn bounding_box(&self, _: f32, _: f32) -> Option<AABB> {
         Some(AABB::new(self.box_min, self.box_max))
     }
+
+    // Sampled as a light through its sides, each picked with equal probability
+    fn pdf_value(&self, origin: &Point3, v: &Vec3) -> f32 {
+        self.sides.pdf_value(origin, v)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.sides.random(origin)
+    }
+
+    fn is_light(&self) -> bool {
+        self.sides.objects().iter().any(|side| side.is_light())
+    }
 }
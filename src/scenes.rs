@@ -1,16 +1,29 @@
 use std::sync::Arc;
 
-use image::{ImageBuffer, Rgb};
+use image::{ImageBuffer, Rgb, Rgba};
 
+use crate::aabb::AABB;
 use crate::bvh::BVHNode;
 use crate::camera::Camera;
+use crate::curve::{Curve, CurveBasis, CurveMode, Curves};
+use crate::fog::Fog;
+use crate::grid::VoxelGrid;
+use crate::heightfield::Heightfield;
 use crate::hittable::HitModel;
-use crate::instances::{FlipFace, RotateY, Translate};
+use crate::instances::{Cutout, FlipFace, RotateY, Translate};
+use crate::light::Light;
+use crate::material::{Conductor, Dielectric, Emission};
+use crate::mesh::{Mesh, TriangleMesh};
 use crate::perlin::Perlin;
+use crate::phase::PhaseFunction;
+use crate::principled::Principled;
 use crate::rect::{Box, XYRect, XZRect, YZRect};
+use crate::sdf::{SdfNode, SdfObject};
 use crate::sphere::Sphere;
+use crate::subdivision::{subdivide, Scheme};
+use crate::thinfilm::ThinFilm;
 use crate::vec3::{Color, Point3};
-use crate::volumes::Constant;
+use crate::volumes::{Constant, DensityField, Heterogeneous, Interior, Subsurface, VolumeEmission};
 use crate::{color, utility::*, ASPECT_RATIO};
 use crate::{
     hittable::HittableList, material::Surface, sphere::MovingSphere, texture::SurfaceTexture,
@@ -34,7 +47,7 @@ pub fn random_scene<'a>() -> (HittableList<MovingSphere<'a>>, Camera, Color) {
     let checkered = SurfaceTexture::Checkered(Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
     let ground_material = Surface::Lambertian(checkered);
 
-    let material1 = Surface::Dielectric(1.5);
+    let material1 = Surface::Dielectric(Dielectric::new(1.5));
     let material2 = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.4, 0.2, 0.1)));
     let material3 = Surface::Metal(Vec3::new(0.7, 0.6, 0.5), 0.0);
 
@@ -76,7 +89,7 @@ pub fn random_scene<'a>() -> (HittableList<MovingSphere<'a>>, Camera, Color) {
                     world.add(MovingSphere::new(center, center, 0.0, 1.0, 0.2, sphere_material));
                 } else {
                     // glass
-                    let sphere_material = Surface::Dielectric(1.5);
+                    let sphere_material = Surface::Dielectric(Dielectric::new(1.5));
                     world.add(MovingSphere::new(center, center, 0.0, 1.0, 0.2, sphere_material));
                 }
             }
@@ -240,7 +253,7 @@ pub fn cornell_box<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
     // let box1 = HitModel::Translate(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
     // objects.add(box1);
 
-    // let glass = Surface::Dielectric(1.5);
+    // let glass = Surface::Dielectric(Dielectric::new(1.5));
     // objects.add(HitModel::Sphere(Sphere::new(Point3::new(190.0, 90.0, 190.0), 90.0, glass)));
 
     let aluminum = Surface::Metal(Color::new(0.8, 0.85, 0.88), 0.0);
@@ -300,7 +313,8 @@ pub fn cornell_smoke<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
     (objects, camera, background)
 }
 
-pub fn final_scene<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+// Thin mist over all of it, handed back as the fog
+pub fn final_scene<'a>() -> (HittableList<HitModel<'a>>, Camera, Color, Fog) {
     let mut objects = HittableList::new();
     let mut boxes1 = Vec::new();
 
@@ -356,7 +370,7 @@ pub fn final_scene<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
     objects.add(HitModel::Sphere(Sphere::new(
         Vec3::new(260.0, 150.0, 45.0),
         50.0,
-        Surface::Dielectric(1.5),
+        Surface::Dielectric(Dielectric::new(1.5)),
     )));
     objects.add(HitModel::Sphere(Sphere::new(
         Vec3::new(0.0, 150.0, 145.0),
@@ -364,17 +378,12 @@ pub fn final_scene<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
         Surface::Metal(Vec3::new(0.8, 0.8, 0.9), 1.0),
     )));
 
-    let boundary = HitModel::Sphere(Sphere::new(
+    let subsurface = Dielectric::new(1.5).interior(Interior::new(0.2, Vec3::new(0.2, 0.4, 0.9)));
+    objects.add(HitModel::Sphere(Sphere::new(
         Vec3::new(360.0, 150.0, 145.0),
         70.0,
-        Surface::Dielectric(1.5),
-    ));
-    objects.add(boundary.clone());
-    objects.add(HitModel::Constant(Constant::new(0.2, boundary, Vec3::new(0.2, 0.4, 0.9))));
-
-    let boundary =
-        HitModel::Sphere(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 5000.0, Surface::Dielectric(1.5)));
-    objects.add(HitModel::Constant(Constant::new(0.0001, boundary, Vec3::new(1.0, 1.0, 1.0))));
+        Surface::Dielectric(subsurface),
+    )));
 
     let perlin = SurfaceTexture::Noise(Perlin::new(), 0.1);
     objects.add(HitModel::Sphere(Sphere::new(
@@ -402,7 +411,9 @@ pub fn final_scene<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 
-    (objects, camera, background)
+    let mist = Fog::homogeneous(0.0001, Color::new(1.0, 1.0, 1.0)).scattering(true);
+
+    (objects, camera, background, mist)
 }
 
 pub fn random_bvh<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
@@ -422,7 +433,7 @@ pub fn random_bvh<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
     let checkered = SurfaceTexture::Checkered(Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9));
     let ground_material = Surface::Lambertian(checkered);
 
-    let material1 = Surface::Dielectric(1.5);
+    let material1 = Surface::Dielectric(Dielectric::new(1.5));
     let material2 = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.4, 0.2, 0.1)));
     let material3 = Surface::Metal(Vec3::new(0.7, 0.6, 0.5), 0.0);
 
@@ -478,7 +489,7 @@ pub fn random_bvh<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
                     ))));
                 } else {
                     // glass
-                    let sphere_material = Surface::Dielectric(1.5);
+                    let sphere_material = Surface::Dielectric(Dielectric::new(1.5));
                     world.push(Arc::new(HitModel::MovingSphere(MovingSphere::new(
                         center,
                         center,
@@ -523,3 +534,1254 @@ pub fn random_bvh<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
 
     (objects, camera, background)
 }
+
+pub fn sdf_shapes<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 30.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Rounded box smoothly blended into a sphere
+    let blob = SdfNode::RoundBox(Vec3::new(0.7, 0.7, 0.7), 0.15)
+        .smooth_union(SdfNode::Sphere(0.6).translate(Vec3::new(0.0, 0.9, 0.0)), 0.3)
+        .translate(Vec3::new(-3.0, 0.85, 0.0));
+    let blob_box = AABB::new(Point3::new(-4.0, 0.0, -1.0), Point3::new(-2.0, 2.2, 1.0));
+    let red = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.65, 0.05, 0.05)));
+    objects.add(HitModel::Sdf(SdfObject::new(blob, blob_box, red)));
+
+    let mandelbulb = SdfNode::Mandelbulb { power: 8.0, iterations: 12 }
+        .scale(1.1)
+        .translate(Vec3::new(0.0, 1.2, 0.0));
+    let mandelbulb_box = AABB::new(Point3::new(-1.4, -0.2, -1.4), Point3::new(1.4, 2.6, 1.4));
+    let gold = Surface::Metal(Color::new(0.8, 0.6, 0.2), 0.1);
+    objects.add(HitModel::Sdf(SdfObject::new(mandelbulb, mandelbulb_box, gold)));
+
+    let twisted = SdfNode::Cuboid(Vec3::new(0.4, 1.0, 0.4))
+        .twist(1.5)
+        .noise_displace(Perlin::new(), 0.05, 4.0)
+        .translate(Vec3::new(3.0, 1.05, 0.0));
+    let twisted_box = AABB::new(Point3::new(2.3, 0.0, -0.7), Point3::new(3.7, 2.2, 0.7));
+    let white = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.73, 0.73, 0.73)));
+    objects.add(HitModel::Sdf(SdfObject::new(twisted, twisted_box, white)));
+
+    (objects, camera, background)
+}
+
+pub fn terrain<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 250.0, -600.0);
+    let lookat = Vec3::new(0.0, 40.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.7, 0.8, 1.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    // Same footprint as the box field in `final_scene`, as a single primitive
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::Heightfield(Heightfield::from_noise(
+        &Perlin::new(),
+        256,
+        4.0,
+        Point3::new(-500.0, 0.0, -500.0),
+        Vec3::new(1000.0, 150.0, 1000.0),
+        ground,
+    )));
+
+    (objects, camera, background)
+}
+
+pub fn grass<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 1.5, 6.0);
+    let lookat = Vec3::new(0.0, 0.4, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 6.0;
+    let aperture = 0.0;
+    let vfov = 30.0;
+    let background = Vec3::new(0.7, 0.8, 1.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let soil = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.3, 0.2, 0.1)));
+    objects.add(HitModel::XZRect(XZRect::new(-10.0, 10.0, -10.0, 10.0, 0.0, soil)));
+
+    // Tapered blades that lean and bend in a random direction
+    let mut rng = rand::thread_rng();
+    let mut blades = Vec::new();
+    for _ in 0..5000 {
+        let base = Point3::new(
+            random_double_range(&mut rng, -2.0, 2.0),
+            0.0,
+            random_double_range(&mut rng, -2.0, 2.0),
+        );
+        let height = random_double_range(&mut rng, 0.4, 0.9);
+        let lean = Vec3::new(
+            random_double_range(&mut rng, -0.3, 0.3),
+            0.0,
+            random_double_range(&mut rng, -0.3, 0.3),
+        );
+
+        let control_points = vec![
+            base - Vec3::new(0.0, 0.1, 0.0),
+            base,
+            base + Vec3::new(0.0, height * 0.5, 0.0) + lean * 0.3,
+            base + Vec3::new(0.0, height, 0.0) + lean,
+            base + Vec3::new(0.0, height * 1.1, 0.0) + lean * 1.6,
+        ];
+        blades.push(Curve::new(control_points, CurveBasis::BSpline, CurveMode::Flat, 0.03, 0.0));
+    }
+
+    let blade_surface = Surface::Hair(Color::new(0.2, 0.6, 0.1), 0.3);
+    objects.add(HitModel::Curves(Curves::new(&blades, blade_surface)));
+
+    // A single tuft of round, hair-like fibers
+    let mut fibers = Vec::new();
+    for _ in 0..500 {
+        let offset = random_unit_disk(&mut rng) * 0.2;
+        let root = Point3::new(offset.x(), 0.0, offset.y() + 1.5);
+        let tip = root + Vec3::new(offset.x() * 2.0, 1.0, offset.y() * 2.0);
+        let control_points =
+            vec![root, root + Vec3::new(0.0, 0.5, 0.0), tip - Vec3::new(0.0, 0.2, 0.0), tip];
+        fibers.push(Curve::new(control_points, CurveBasis::Bezier, CurveMode::Round, 0.01, 0.005));
+    }
+
+    let hair = Surface::Hair(Color::new(0.55, 0.35, 0.15), 0.2);
+    objects.add(HitModel::Curves(Curves::new(&fibers, hair)));
+
+    (objects, camera, background)
+}
+
+pub fn subdivision<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 30.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // The same cube cage, smooth and with its top face creased
+    let white = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.73, 0.73, 0.73)));
+    let cage = Mesh::cube(Point3::new(-4.0, 0.0, -1.0), Point3::new(-2.0, 2.0, 1.0));
+    let smooth = subdivide(&cage, Scheme::CatmullClark, 4);
+    objects.add(HitModel::Mesh(TriangleMesh::new(&smooth, white)));
+
+    let mut cage = Mesh::cube(Point3::new(-1.0, 0.0, -1.0), Point3::new(1.0, 2.0, 1.0));
+    for (a, b) in [(2, 6), (6, 7), (7, 3), (3, 2)] {
+        cage.add_crease(a, b, f32::INFINITY);
+    }
+    for (a, b) in [(0, 2), (1, 3), (4, 6), (5, 7)] {
+        cage.add_crease(a, b, 1.5);
+    }
+    let creased = subdivide(&cage, Scheme::CatmullClark, 4);
+    let red = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.65, 0.05, 0.05)));
+    objects.add(HitModel::Mesh(TriangleMesh::new(&creased, red)));
+
+    // Loop subdivision of an octahedron
+    let octahedron = Mesh::new(
+        vec![
+            Point3::new(3.0, 0.1, 0.0),
+            Point3::new(4.2, 1.3, 0.0),
+            Point3::new(3.0, 1.3, 1.2),
+            Point3::new(1.8, 1.3, 0.0),
+            Point3::new(3.0, 1.3, -1.2),
+            Point3::new(3.0, 2.5, 0.0),
+        ],
+        vec![
+            vec![0, 1, 2],
+            vec![0, 2, 3],
+            vec![0, 3, 4],
+            vec![0, 4, 1],
+            vec![5, 2, 1],
+            vec![5, 3, 2],
+            vec![5, 4, 3],
+            vec![5, 1, 4],
+        ],
+    );
+    let gold = Surface::Metal(Color::new(0.8, 0.6, 0.2), 0.1);
+    objects.add(HitModel::Mesh(TriangleMesh::new(&subdivide(&octahedron, Scheme::Loop, 4), gold)));
+
+    (objects, camera, background)
+}
+
+pub fn displacement<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 30.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Noise-displaced ground, tessellated from a single quad
+    let ground = Mesh::new(
+        vec![
+            Point3::new(-6.0, 0.0, -6.0),
+            Point3::new(-6.0, 0.0, 6.0),
+            Point3::new(6.0, 0.0, 6.0),
+            Point3::new(6.0, 0.0, -6.0),
+        ],
+        vec![vec![0, 1, 2, 3]],
+    );
+    let bumps = SurfaceTexture::Noise(Perlin::new(), 2.0);
+    let ground = ground.tessellate(0.1).displace(&bumps, 0.3);
+    let green = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::Mesh(TriangleMesh::new(&ground, green)));
+
+    // Marbled ridges pushed out of a smooth subdivided cube
+    let cage = Mesh::cube(Point3::new(-1.0, 0.5, -1.0), Point3::new(1.0, 2.5, 1.0));
+    let ridges = SurfaceTexture::Noise(Perlin::new(), 8.0);
+    let rock = subdivide(&cage, Scheme::CatmullClark, 2).tessellate(0.04).displace(&ridges, 0.15);
+    let white = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.73, 0.73, 0.73)));
+    objects.add(HitModel::Mesh(TriangleMesh::new(&rock, white)));
+
+    (objects, camera, background)
+}
+
+pub fn colored_glass<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 30.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let checker = SurfaceTexture::Checkered(Color::new(0.2, 0.2, 0.2), Color::new(0.9, 0.9, 0.9));
+    objects.add(HitModel::XZRect(XZRect::new(
+        -50.0,
+        50.0,
+        -50.0,
+        50.0,
+        0.0,
+        Surface::Lambertian(checker),
+    )));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Clear glass, a surface tint, and a liquid whose color deepens with thickness
+    let clear = Dielectric::new(1.5);
+    let tinted = Dielectric::new(1.5).tint(Color::new(0.6, 0.8, 1.0));
+    let wine = Dielectric::new(1.33).absorption(Color::new(0.2, 2.0, 1.5));
+
+    for (x, glass) in [(-3.0, clear), (0.0, tinted), (3.0, wine)] {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(x, 1.0, 0.0),
+            1.0,
+            Surface::Dielectric(glass),
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+pub fn rough_materials<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 35.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Metals from polished to rough in front, rough glass of increasing roughness behind
+    let metals = [
+        Conductor::gold(0.1),
+        Conductor::copper(0.3),
+        Conductor::aluminum(0.5),
+        Conductor::gold(0.8),
+    ];
+    for (i, metal) in metals.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-3.3 + 2.2 * i as f32, 0.9, 1.5),
+            0.9,
+            Surface::RoughConductor(*metal),
+        )));
+    }
+
+    for (i, roughness) in [0.05, 0.2, 0.4].iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-2.2 + 2.2 * i as f32, 0.9, -1.5),
+            0.9,
+            Surface::RoughDielectric(Dielectric::new(1.5), *roughness),
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+// Materials for `principled_spheres`, which borrows them
+pub fn principled_materials<'a>() -> Vec<Principled<'a>> {
+    let checker = SurfaceTexture::Checkered(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+
+    vec![
+        // Red plastic under a clear coat
+        Principled::new(SurfaceTexture::Solid(Color::new(0.7, 0.1, 0.1)))
+            .roughness(SurfaceTexture::constant(0.4))
+            .clearcoat(SurfaceTexture::constant(1.0)),
+        // Brushed gold
+        Principled::new(SurfaceTexture::Solid(Color::new(1.0, 0.76, 0.33)))
+            .metallic(SurfaceTexture::constant(1.0))
+            .roughness(SurfaceTexture::constant(0.3)),
+        // Velvet-like cloth
+        Principled::new(SurfaceTexture::Solid(Color::new(0.2, 0.1, 0.5)))
+            .roughness(SurfaceTexture::constant(1.0))
+            .sheen(SurfaceTexture::constant(1.0)),
+        // Frosted glass
+        Principled::new(SurfaceTexture::Solid(Color::new(0.9, 1.0, 0.9)))
+            .roughness(SurfaceTexture::constant(0.15))
+            .transmission(SurfaceTexture::constant(1.0))
+            .ior(SurfaceTexture::constant(1.5)),
+        // Metal inlaid in a dielectric, driven by a texture
+        Principled::new(SurfaceTexture::Solid(Color::new(0.8, 0.8, 0.8)))
+            .metallic(checker)
+            .roughness(SurfaceTexture::constant(0.2)),
+    ]
+}
+
+pub fn principled_spheres<'a>(
+    materials: &'a [Principled<'a>],
+) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    let spacing = 2.2;
+    let offset = -0.5 * spacing * (materials.len() - 1) as f32;
+    for (i, material) in materials.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(offset + spacing * i as f32, 1.0, 0.0),
+            1.0,
+            Surface::Principled(material),
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+pub fn diffuse_materials<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Lambertian against increasingly rough clay, then smooth and rough plastic
+    let clay = SurfaceTexture::Solid(Color::new(0.8, 0.5, 0.3));
+    let red = SurfaceTexture::Solid(Color::new(0.7, 0.1, 0.1));
+    let materials = [
+        Surface::Lambertian(clay),
+        Surface::OrenNayar(clay, 20.0),
+        Surface::OrenNayar(clay, 60.0),
+        Surface::Plastic(red, 1.5, 0.05),
+        Surface::Plastic(red, 1.5, 0.4),
+    ];
+
+    for (i, material) in materials.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-4.4 + 2.2 * i as f32, 1.0, 0.0),
+            1.0,
+            *material,
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+// Base surfaces for `bump_maps`, which wraps them
+pub fn bump_bases<'a>() -> Vec<Surface<'a>> {
+    vec![
+        Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.8, 0.5, 0.3))),
+        Surface::Plastic(SurfaceTexture::Solid(Color::new(0.7, 0.1, 0.1)), 1.5, 0.2),
+        Surface::RoughConductor(Conductor::gold(0.25)),
+    ]
+}
+
+// Tangent-space normal map of a grid of smooth bumps, `size` pixels square
+pub fn wave_normal_map(size: u32, waves: f32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(size, size, |i, j| {
+        let x = 2.0 * PI * waves * i as f32 / size as f32;
+        let y = 2.0 * PI * waves * j as f32 / size as f32;
+
+        // Slopes of sin(x) sin(y), with v running up the image
+        let normal = crate::vec3::unit_vector(&Vec3::new(
+            -0.5 * x.cos() * y.sin(),
+            0.5 * x.sin() * y.cos(),
+            1.0,
+        ));
+        let encode = |c: f32| (255.0 * clamp(0.5 * c + 0.5, 0.0, 1.0)) as u8;
+
+        Rgb([encode(normal.x()), encode(normal.y()), encode(normal.z())])
+    })
+}
+
+pub fn bump_maps<'a>(
+    bases: &'a [Surface<'a>],
+    normal_map: &'a ImageBuffer<Rgb<u8>, Vec<u8>>,
+) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 6.0, 10.0);
+    let lookat = Vec3::new(0.0, 0.5, -2.4);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let waves = SurfaceTexture::Image {
+        buffer: normal_map,
+        width: normal_map.width(),
+        height: normal_map.height(),
+    };
+
+    let ground = Surface::NormalMap(&bases[0], waves);
+    objects.add(HitModel::XZRect(XZRect::new(-10.0, 10.0, -10.0, 10.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Each base surface, left smooth, bumped by noise, then normal mapped
+    let noise = SurfaceTexture::Noise(Perlin::new(), 4.0);
+    for (i, base) in bases.iter().enumerate() {
+        let surfaces = [*base, Surface::Bump(base, noise, 0.04), Surface::NormalMap(base, waves)];
+        for (j, surface) in surfaces.iter().enumerate() {
+            objects.add(HitModel::Sphere(Sphere::new(
+                Point3::new(-2.4 + 2.4 * j as f32, 0.8, -2.4 * i as f32),
+                0.8,
+                *surface,
+            )));
+        }
+    }
+
+    (objects, camera, background)
+}
+
+// RGBA leaf for `cutouts`: a green ellipse with darker veins, transparent around it
+pub fn leaf_texture(size: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(size, size, |i, j| {
+        let x = 2.0 * i as f32 / size as f32 - 1.0;
+        let y = 2.0 * j as f32 / size as f32 - 1.0;
+
+        // Pointed at both ends, narrowing towards the tip
+        let half_width = 0.45 * (1.0 - y * y) * (1.0 - 0.3 * y);
+        if x.abs() > half_width {
+            return Rgba([0, 0, 0, 0]);
+        }
+
+        let vein = x.abs() < 0.02 || ((y - 1.5 * x.abs()) * 8.0).fract().abs() < 0.08;
+        if vein {
+            Rgba([60, 110, 30, 255])
+        } else {
+            Rgba([80, 160, 40, 255])
+        }
+    })
+}
+
+pub fn cutouts<'a>(
+    leaf: &'a ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.5, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.73, 0.73, 0.73)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Leaf cards, masked by the image's alpha channel
+    let leaf =
+        SurfaceTexture::ImageAlpha { buffer: leaf, width: leaf.width(), height: leaf.height() };
+    for (i, angle) in [-30.0, 15.0, 50.0].iter().enumerate() {
+        let card =
+            HitModel::XYRect(XYRect::new(-1.0, 1.0, 0.0, 2.0, 0.0, Surface::Lambertian(leaf)));
+        let card = HitModel::Cutout(Cutout::new(card, leaf));
+        objects.add(HitModel::Translate(Translate::new(
+            HitModel::RotateY(RotateY::new(card, *angle)),
+            Vec3::new(-3.0 + 1.2 * i as f32, 0.5, -0.5 * i as f32),
+        )));
+    }
+
+    // Fence from a scalar checker mask, and a half-transparent screen
+    let fence = HitModel::XYRect(XYRect::new(
+        0.5,
+        4.0,
+        0.0,
+        2.5,
+        1.0,
+        Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.6, 0.4, 0.2))),
+    ));
+    let mask = SurfaceTexture::Checkered(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+    objects.add(HitModel::Cutout(Cutout::new(fence, mask)));
+
+    let screen = HitModel::XYRect(XYRect::new(
+        -1.0,
+        1.0,
+        0.0,
+        2.5,
+        2.0,
+        Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.2, 0.3, 0.8))),
+    ));
+    objects.add(HitModel::Cutout(Cutout::new(screen, SurfaceTexture::constant(0.5))));
+
+    (objects, camera, background)
+}
+
+// Surfaces blended by `mixed_materials`: bare metal, rust, paint and a glowing layer
+pub fn mix_layers<'a>() -> Vec<Surface<'a>> {
+    vec![
+        Surface::RoughConductor(Conductor::aluminum(0.2)),
+        Surface::OrenNayar(SurfaceTexture::Solid(Color::new(0.45, 0.18, 0.06)), 40.0),
+        Surface::Plastic(SurfaceTexture::Solid(Color::new(0.1, 0.3, 0.6)), 1.5, 0.3),
+        Surface::DiffuseLight(SurfaceTexture::Solid(Color::new(4.0, 2.0, 0.5))),
+    ]
+}
+
+// Rust spreading over metal along a noise mask, kept by the caller so other mixes can nest it
+pub fn rusted_metal<'a>(layers: &'a [Surface<'a>]) -> Surface<'a> {
+    Surface::Mix(&layers[0], &layers[1], SurfaceTexture::Noise(Perlin::new(), 2.0))
+}
+
+pub fn mixed_materials<'a>(
+    layers: &'a [Surface<'a>],
+    rusted: &'a Surface<'a>,
+) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Rusted metal, paint chequered over it, and paint with glowing patches
+    let checker = SurfaceTexture::Checkered(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+    let materials = [
+        *rusted,
+        Surface::Mix(rusted, &layers[2], checker),
+        Surface::Mix(&layers[2], &layers[3], checker),
+    ];
+
+    for (i, material) in materials.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-2.4 + 2.4 * i as f32, 1.0, 0.0),
+            1.0,
+            *material,
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+// Non-emissive surfaces that `emissive_materials` makes glow
+pub fn emissive_bases<'a>() -> Vec<Surface<'a>> {
+    vec![
+        Surface::Dielectric(Dielectric::new(1.5)),
+        Surface::RoughConductor(Conductor::copper(0.3)),
+        Surface::Lambertian(SurfaceTexture::Solid(Color::new(0.0, 0.0, 0.0))),
+    ]
+}
+
+pub fn emissive_materials<'a>(
+    bases: &'a [Surface<'a>],
+) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    // Two-sided panel, so no `FlipFace` is needed to light the scene below it
+    let panel = Emission::from_watts(Color::new(1.0, 1.0, 1.0), 2500.0, 36.0, true);
+    objects.add(HitModel::XZRect(XZRect::new(
+        -3.0,
+        3.0,
+        -3.0,
+        3.0,
+        8.0,
+        Surface::Emissive(&bases[2], panel),
+    )));
+
+    // Glowing glass, emissive metal and a matte lamp given in nits
+    let glow = Emission::new(SurfaceTexture::Solid(Color::new(1.0, 0.4, 0.1))).strength(0.5);
+    let materials = [
+        Surface::Emissive(&bases[0], glow),
+        Surface::Emissive(&bases[1], glow.strength(0.2)),
+        Surface::Emissive(&bases[2], Emission::from_nits(Color::new(0.6, 0.8, 1.0), 2000.0)),
+    ];
+
+    for (i, material) in materials.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-2.4 + 2.4 * i as f32, 1.0, 0.0),
+            1.0,
+            *material,
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+pub fn blackbody_lights<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+
+    // Faint overcast sky
+    let background = color::blackbody(9000.0, 0.05);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let white = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.73, 0.73, 0.73)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, white)));
+
+    // Candle, tungsten, daylight and blue sky temperatures, each over its own sphere
+    for (i, temperature) in [1900.0, 3200.0, 5500.0, 10000.0].iter().enumerate() {
+        let x = -3.3 + 2.2 * i as f32;
+        let light =
+            Surface::DiffuseLight(SurfaceTexture::Solid(color::blackbody(*temperature, 12.0)));
+        objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+            x - 0.6,
+            x + 0.6,
+            -0.6,
+            0.6,
+            4.0,
+            light,
+        )))));
+        objects.add(HitModel::Sphere(Sphere::new(Point3::new(x, 1.0, 0.0), 0.9, white)));
+    }
+
+    (objects, camera, background)
+}
+
+// Marble, skin, milk and wax
+pub fn subsurface_spheres<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    let media = [
+        (Color::new(0.999, 0.998, 0.995), Color::new(0.3, 0.25, 0.2)),
+        (Color::new(0.99, 0.9, 0.8), Color::new(0.4, 0.15, 0.08)),
+        (Color::new(0.999, 0.999, 0.997), Color::new(0.1, 0.1, 0.08)),
+        (Color::new(0.995, 0.97, 0.85), Color::new(0.5, 0.35, 0.15)),
+    ];
+    for (i, (albedo, mean_free_path)) in media.iter().enumerate() {
+        let medium = Subsurface::new(*albedo, *mean_free_path, 1.4);
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-3.3 + 2.2 * i as f32, 1.0, 0.0),
+            0.9,
+            Surface::Subsurface(medium),
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+pub fn thin_films<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.1, 0.1, 0.12);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let checker = SurfaceTexture::Checkered(Color::new(0.1, 0.1, 0.1), Color::new(0.8, 0.8, 0.8));
+    objects.add(HitModel::XZRect(XZRect::new(
+        -50.0,
+        50.0,
+        -50.0,
+        50.0,
+        0.0,
+        Surface::Lambertian(checker),
+    )));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Soap bubble of uneven thickness, coated glass, and steel tempered to a straw-colored oxide
+    let swirls = SurfaceTexture::Noise(Perlin::new(), 3.0);
+    let materials = [
+        Surface::Dielectric(
+            Dielectric::new(1.0).film(ThinFilm::new(800.0, 1.33).thickness_texture(swirls)),
+        ),
+        Surface::Dielectric(Dielectric::new(1.5).film(ThinFilm::new(350.0, 2.0))),
+        Surface::RoughConductor(
+            Conductor::new(Color::new(2.9, 2.9, 2.7), Color::new(3.0, 3.0, 3.0), 0.15)
+                .film(ThinFilm::new(120.0, 2.5)),
+        ),
+    ];
+
+    for (i, material) in materials.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-2.4 + 2.4 * i as f32, 1.0, 0.0),
+            1.0,
+            *material,
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+// Tangent rotations for `brushed_metal`: a quarter turn, and swirls
+pub fn brushed_rotations<'a>() -> Vec<SurfaceTexture<'a>> {
+    vec![SurfaceTexture::constant(0.25), SurfaceTexture::Noise(Perlin::new(), 1.0)]
+}
+
+pub fn brushed_metal<'a>(
+    rotations: &'a [SurfaceTexture<'a>],
+) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Isotropic steel, then brushed around the poles, from pole to pole, and along swirls
+    let steel = Conductor::new(Color::new(2.9, 2.9, 2.7), Color::new(3.0, 3.0, 3.0), 0.3);
+    let brushed = steel.anisotropic(0.05, 0.5);
+    let materials = [
+        Surface::RoughConductor(steel),
+        Surface::RoughConductor(brushed),
+        Surface::RoughConductor(brushed.rotation(&rotations[0])),
+        Surface::RoughConductor(brushed.rotation(&rotations[1])),
+    ];
+
+    for (i, material) in materials.iter().enumerate() {
+        objects.add(HitModel::Sphere(Sphere::new(
+            Point3::new(-3.3 + 2.2 * i as f32, 1.0, 0.0),
+            0.9,
+            *material,
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+pub fn phase_functions<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    // Light above and behind the media, so forward scattering ones glow and backward ones stay dim
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -8.0, -4.0, 8.0, light,
+    )))));
+
+    let phases = [
+        PhaseFunction::Isotropic,
+        PhaseFunction::HenyeyGreenstein(0.8),
+        PhaseFunction::HenyeyGreenstein(-0.6),
+        PhaseFunction::DoubleHenyeyGreenstein(0.9, -0.5, 0.7),
+        PhaseFunction::Rayleigh,
+    ];
+
+    for (i, phase) in phases.iter().enumerate() {
+        let boundary = HitModel::Sphere(Sphere::new(
+            Point3::new(-4.0 + 2.0 * i as f32, 1.0, 0.0),
+            0.9,
+            Surface::Dielectric(Dielectric::new(1.5)),
+        ));
+        objects.add(HitModel::Constant(Constant::with_phase(
+            1.5,
+            boundary,
+            Color::new(0.9, 0.9, 0.9),
+            *phase,
+        )));
+    }
+
+    (objects, camera, background)
+}
+
+pub fn smoke_and_clouds<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.5, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.05, 0.05, 0.08);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Cloud of turbulence inside a sphere, scattering forward like water droplets
+    let cloud = DensityField::noise(4.0, 4.0);
+    let boundary = HitModel::Sphere(Sphere::new(
+        Point3::new(-2.0, 2.0, 0.0),
+        1.5,
+        Surface::Dielectric(Dielectric::new(1.0)),
+    ));
+    objects.add(HitModel::Heterogeneous(
+        Heterogeneous::new(boundary, cloud, Color::new(0.95, 0.95, 0.95))
+            .phase(PhaseFunction::HenyeyGreenstein(0.6)),
+    ));
+
+    // Smoke plume rising and spreading, with wisps from noise
+    let (min, max) = (Point3::new(0.8, 0.0, -1.2), Point3::new(3.2, 4.0, 1.2));
+    let perlin = Perlin::new();
+    let plume = VoxelGrid::from_fn(48, 80, 48, min, max, |p| {
+        let radius = 0.2 + 0.25 * p.y();
+        let offset = Vec3::new(p.x() - 2.0, 0.0, p.z());
+        let falloff = (-offset.length_squared() / (radius * radius)).exp();
+        let wisps = perlin.turb(&(*p * 3.0));
+        8.0 * falloff * wisps * (1.0 - p.y() / 4.0)
+    });
+    let boundary = HitModel::Box(Box::new(min, max, Surface::Dielectric(Dielectric::new(1.0))));
+    objects.add(HitModel::Heterogeneous(Heterogeneous::new(
+        boundary,
+        DensityField::Grid(plume),
+        Color::new(0.6, 0.6, 0.6),
+    )));
+
+    (objects, camera, background)
+}
+
+// A loaded density grid (see `VoxelGrid::load_vol`), refit into a 3-unit cube, turned and set
+// on the ground
+pub fn volume_grid<'a>(grid: VoxelGrid) -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.5, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.05, 0.05, 0.08);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    let (min, max) = (Point3::new(-1.5, 0.0, -1.5), Point3::new(1.5, 3.0, 1.5));
+    let grid = grid.with_bounds(min, max);
+    let boundary = HitModel::Box(Box::new(min, max, Surface::Dielectric(Dielectric::new(1.0))));
+    let medium = HitModel::Heterogeneous(Heterogeneous::new(
+        boundary,
+        DensityField::Grid(grid),
+        Color::new(0.8, 0.8, 0.8),
+    ));
+    objects.add(HitModel::Translate(Translate::new(
+        HitModel::RotateY(RotateY::new(medium, 30.0)),
+        Vec3::new(0.0, 0.0, -0.5),
+    )));
+
+    (objects, camera, background)
+}
+
+// Wine inside a glass ball, milk-glass scattering through its interior, and an ice cube floating
+// in water: the ice outranks the water, so the water's surface inside the cube is ignored
+pub fn nested_dielectrics<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.3, 0.35, 0.45);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Checkered(
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(7.0, 7.0, 7.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // A glass ball with a core of wine, which refracts against the glass rather than air. At
+    // equal priority the wine, entered last, fills the space inside it.
+    let glass = Surface::Dielectric(Dielectric::new(1.5));
+    let wine = Surface::Dielectric(Dielectric::new(1.33).absorption(Color::new(0.2, 2.0, 1.5)));
+    objects.add(HitModel::Sphere(Sphere::new(Point3::new(-2.8, 1.0, 0.0), 1.0, glass)));
+    objects.add(HitModel::Sphere(Sphere::new(Point3::new(-2.8, 1.0, 0.0), 0.9, wine)));
+
+    let milk = Interior::new(4.0, Color::new(0.95, 0.95, 0.9));
+    objects.add(HitModel::Sphere(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Surface::Dielectric(Dielectric::new(1.5).interior(milk)),
+    )));
+
+    let water = Dielectric::new(1.33).absorption(Color::new(0.3, 0.08, 0.05));
+    let ice = Dielectric::new(1.31).priority(1);
+    objects.add(HitModel::Box(Box::new(
+        Point3::new(1.6, 0.0, -1.0),
+        Point3::new(4.0, 1.2, 1.0),
+        Surface::Dielectric(water),
+    )));
+    let cube = HitModel::Box(Box::new(
+        Point3::new(-0.4, -0.4, -0.4),
+        Point3::new(0.4, 0.4, 0.4),
+        Surface::Dielectric(ice),
+    ));
+    objects.add(HitModel::Translate(Translate::new(
+        HitModel::RotateY(RotateY::new(cube, 25.0)),
+        Vec3::new(2.8, 1.1, 0.0),
+    )));
+
+    (objects, camera, background)
+}
+
+// Fireball of turbulent smoke glowing from a blackbody temperature grid, beside a candle whose
+// flame is a small homogeneous medium of constant emission
+pub fn fire<'a>() -> (HittableList<HitModel<'a>>, Camera, Color) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.5, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(0.5, 0.5, 0.5)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    // Hottest at the core, cooling with turbulent tongues towards the edge
+    let center = Point3::new(-1.5, 2.0, 0.0);
+    let radius = 1.5;
+    let perlin = Perlin::new();
+    let offset = Vec3::new(radius, radius, radius);
+    let temperature = VoxelGrid::from_fn(48, 48, 48, center - offset, center + offset, |p| {
+        let falloff = 1.0 - (*p - center).length() / radius;
+        2500.0 * falloff.max(0.0).sqrt() * (0.4 + perlin.turb(&(*p * 2.0)))
+    });
+    let smoke = DensityField::noise(2.0, 6.0);
+    let boundary =
+        HitModel::Sphere(Sphere::new(center, radius, Surface::Dielectric(Dielectric::new(1.0))));
+    objects.add(HitModel::Heterogeneous(
+        Heterogeneous::new(boundary, smoke, Color::new(0.3, 0.3, 0.3))
+            .emission(VolumeEmission::blackbody(temperature, 50.0)),
+    ));
+
+    let wax = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.9, 0.85, 0.75)));
+    objects.add(HitModel::Box(Box::new(
+        Point3::new(2.2, 0.0, -0.3),
+        Point3::new(2.8, 1.4, 0.3),
+        wax,
+    )));
+
+    let boundary = HitModel::Sphere(Sphere::new(
+        Point3::new(2.5, 1.65, 0.0),
+        0.2,
+        Surface::Dielectric(Dielectric::new(1.0)),
+    ));
+    objects.add(HitModel::Constant(
+        Constant::new(20.0, boundary, Color::new(0.0, 0.0, 0.0))
+            .emission(VolumeEmission::Constant(Color::new(3.0, 1.4, 0.4))),
+    ));
+
+    (objects, camera, background)
+}
+
+// Shafts of light through slats under an area light, scattered by height fog thinning upwards
+// that's handed back as the fog
+pub fn god_rays<'a>() -> (HittableList<HitModel<'a>>, Camera, Color, Fog) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 2.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let light = Surface::DiffuseLight(SurfaceTexture::Solid(Vec3::new(20.0, 20.0, 20.0)));
+    objects.add(HitModel::FlipFace(FlipFace::new(HitModel::XZRect(XZRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    )))));
+
+    let wood = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.3, 0.2, 0.1)));
+    for slat in 0..6 {
+        let x = -4.0 + 1.5 * slat as f32;
+        objects.add(HitModel::Box(Box::new(
+            Point3::new(x, 6.0, -5.0),
+            Point3::new(x + 0.8, 6.2, 5.0),
+            wood,
+        )));
+    }
+
+    let haze = Fog::height(0.08, 0.0, 0.2, Color::new(0.9, 0.9, 0.9)).scattering(true);
+
+    (objects, camera, background, haze)
+}
+
+// Spheres lit by lights without geometry, handed back for `Lights::with_delta`: a spotlight on
+// the left sphere, a warm incandescent bulb of some size by the right one, and faint moonlight
+pub fn lamps<'a>() -> (HittableList<HitModel<'a>>, Camera, Color, Vec<Light>) {
+    let mut objects = HittableList::new();
+
+    let lookfrom = Vec3::new(0.0, 3.0, 12.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfov = 40.0;
+    let background = Vec3::new(0.0, 0.0, 0.0);
+
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, ASPECT_RATIO, aperture, dist_to_focus, 0.0, 0.0);
+
+    let ground = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.48, 0.83, 0.53)));
+    objects.add(HitModel::XZRect(XZRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+
+    let white = Surface::Lambertian(SurfaceTexture::Solid(Vec3::new(0.73, 0.73, 0.73)));
+    let gold = Surface::Metal(Color::new(0.8, 0.6, 0.2), 0.2);
+    objects.add(HitModel::Sphere(Sphere::new(Point3::new(-2.5, 1.0, 0.0), 1.0, white)));
+    objects.add(HitModel::Sphere(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, gold)));
+    objects.add(HitModel::Sphere(Sphere::new(Point3::new(2.5, 1.0, 0.0), 1.0, white)));
+
+    let lights = vec![
+        Light::spot(
+            Point3::new(-4.0, 6.0, 2.0),
+            Point3::new(-2.5, 0.0, 0.0),
+            Color::new(60.0, 60.0, 60.0),
+            20.0,
+            5.0,
+        ),
+        Light::point(Point3::new(4.0, 2.5, 2.0), color::blackbody(2700.0, 15.0), 0.3),
+        Light::directional(Vec3::new(1.0, -1.0, -1.0), Color::new(0.05, 0.06, 0.1), 0.5),
+    ];
+
+    (objects, camera, background, lights)
+}
@@ -5,7 +5,7 @@ use crate::{
     hittable::{HitRecord, Hittable},
     material::Surface,
     ray::Ray,
-    utility::{random_to_sphere, PI},
+    utility::{random_to_sphere, random_unit_vector, PI},
     vec3::{self, Point3, Vec3},
 };
 
@@ -47,16 +47,21 @@ impl Hittable for Sphere<'_> {
             } else {
                 let p = r.at(root);
                 let t = root;
-                let (u, v) = sphere_uv(&p);
                 let normal = (p - self.center) / self.radius;
+                let (u, v) = sphere_uv(&normal);
                 let front_face = HitRecord::face_normal(r, &normal);
 
                 // Surface normal is always against the incident ray
-                if front_face {
-                    Some(HitRecord::new(p, normal, t, u, v, front_face, &self.material))
+                let mut rec = if front_face {
+                    HitRecord::new(p, normal, t, u, v, front_face, &self.material)
                 } else {
-                    Some(HitRecord::new(p, -normal, t, u, v, front_face, &self.material))
-                }
+                    HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+                };
+                let (dpdu, dpdv) = sphere_tangents(&normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
+
+                Some(rec)
             }
         }
     }
@@ -74,8 +79,13 @@ impl Hittable for Sphere<'_> {
             return 0.0;
         }
 
-        let cos_theta_max =
-            1.0 - self.radius * self.radius / (self.center - *origin).length_squared();
+        // From inside, every direction meets the sphere once
+        let dist_squared = (self.center - *origin).length_squared();
+        if dist_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
+
+        let cos_theta_max = (1.0 - self.radius * self.radius / dist_squared).sqrt();
         let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
 
         1.0 / solid_angle
@@ -84,10 +94,18 @@ impl Hittable for Sphere<'_> {
     fn random(&self, origin: &Point3) -> Vec3 {
         let direction = self.center - *origin;
         let dist_squared = direction.length_squared();
+        if dist_squared <= self.radius * self.radius {
+            return random_unit_vector(&mut thread_rng());
+        }
+
         let uvw = vec3::coordinate_system(&direction);
 
         uvw.local_vec(&random_to_sphere(&mut thread_rng(), self.radius, dist_squared))
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -142,16 +160,21 @@ impl Hittable for MovingSphere<'_> {
             } else {
                 let p = r.at(root);
                 let t = root;
-                let (u, v) = sphere_uv(&p);
                 let normal = (p - self.center(r.time())) / self.radius;
+                let (u, v) = sphere_uv(&normal);
                 let front_face = HitRecord::face_normal(r, &normal);
 
                 // Surface normal is always against the incident ray
-                if front_face {
-                    Some(HitRecord::new(p, normal, t, u, v, front_face, &self.material))
+                let mut rec = if front_face {
+                    HitRecord::new(p, normal, t, u, v, front_face, &self.material)
                 } else {
-                    Some(HitRecord::new(p, -normal, t, u, v, front_face, &self.material))
-                }
+                    HitRecord::new(p, -normal, t, u, v, front_face, &self.material)
+                };
+                let (dpdu, dpdv) = sphere_tangents(&normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
+
+                Some(rec)
             }
         }
     }
@@ -172,10 +195,26 @@ impl Hittable for MovingSphere<'_> {
     }
 }
 
-// Returns spherical coordinates mapped to (u, v) in interval [0, 1]
-fn sphere_uv(p: &Point3) -> (f32, f32) {
+// Returns spherical coordinates of a point on the unit sphere mapped to (u, v) in interval [0, 1]
+pub fn sphere_uv(p: &Point3) -> (f32, f32) {
     let theta = (-p.y()).acos();
     let phi = (-p.z()).atan2(p.x()) + PI;
 
     (phi / (2.0 * PI), theta / PI)
 }
+
+// Partial derivatives of the position with respect to the (u, v) of `sphere_uv`, given the
+// outward unit normal. Degenerate (zero) at the poles.
+fn sphere_tangents(normal: &Vec3, radius: f32) -> (Vec3, Vec3) {
+    let (x, y, z) = (normal.x(), normal.y(), normal.z());
+    let rho = (x * x + z * z).sqrt();
+
+    let dpdu = Vec3::new(z, 0.0, -x) * (2.0 * PI * radius);
+    let dpdv = if rho > 0.0 {
+        Vec3::new(-y * x / rho, rho, -y * z / rho) * (PI * radius)
+    } else {
+        Vec3::default()
+    };
+
+    (dpdu, dpdv)
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use crate::{
+    mesh::Mesh,
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Copy, Clone)]
+pub enum Scheme {
+    CatmullClark, // any polygons, produces quads
+    Loop,         // triangles only; other faces are fan-triangulated first
+}
+
+// Refines `mesh` by `levels` steps of the given scheme. Boundary edges always follow the
+// sharp (cubic B-spline) rules; creased edges follow them while their sharpness lasts, losing
+// one unit per level, with fractional sharpness blending the smooth and sharp results. Every
+// level multiplies the face count by four, so a handful of levels is all a render can hold.
+pub fn subdivide(mesh: &Mesh, scheme: Scheme, levels: u32) -> Mesh {
+    let mut mesh = match scheme {
+        Scheme::Loop if !mesh.is_triangle_mesh() => {
+            Mesh { faces: mesh.triangles().iter().map(|t| t.to_vec()).collect(), ..mesh.clone() }
+        }
+        _ => mesh.clone(),
+    };
+
+    for _ in 0..levels {
+        mesh = match scheme {
+            Scheme::CatmullClark => catmull_clark_step(&mesh),
+            Scheme::Loop => loop_step(&mesh),
+        };
+    }
+
+    mesh
+}
+
+struct Edge {
+    vertices: (usize, usize),
+    faces: Vec<usize>,
+    sharpness: f32,
+}
+
+// Edge adjacency of a polygon mesh
+struct Topology {
+    edges: Vec<Edge>,
+    edge_index: HashMap<(usize, usize), usize>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(mesh: &Mesh) -> Self {
+        let mut edges: Vec<Edge> = Vec::new();
+        let mut edge_index = HashMap::new();
+        let mut vertex_edges = vec![Vec::new(); mesh.positions.len()];
+        let mut vertex_faces = vec![Vec::new(); mesh.positions.len()];
+
+        for (f, face) in mesh.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let index = *edge_index.entry(edge_key(a, b)).or_insert_with(|| {
+                    edges.push(Edge { vertices: (a, b), faces: Vec::new(), sharpness: 0.0 });
+                    vertex_edges[a].push(edges.len() - 1);
+                    vertex_edges[b].push(edges.len() - 1);
+                    edges.len() - 1
+                });
+
+                edges[index].faces.push(f);
+                vertex_faces[a].push(f);
+            }
+        }
+
+        for &(a, b, sharpness) in mesh.creases.iter() {
+            if let Some(&index) = edge_index.get(&edge_key(a, b)) {
+                edges[index].sharpness = edges[index].sharpness.max(sharpness);
+            }
+        }
+
+        // Boundary and non-manifold edges are treated as infinitely sharp
+        for edge in edges.iter_mut() {
+            if edge.faces.len() != 2 {
+                edge.sharpness = f32::INFINITY;
+            }
+        }
+
+        Topology { edges, edge_index, vertex_edges, vertex_faces }
+    }
+
+    fn edge(&self, a: usize, b: usize) -> usize {
+        self.edge_index[&edge_key(a, b)]
+    }
+
+    fn other_vertex(&self, edge: usize, v: usize) -> usize {
+        let (a, b) = self.edges[edge].vertices;
+        if a == v {
+            b
+        } else {
+            a
+        }
+    }
+
+    // Applies the crease and corner rules to a vertex that has `smooth` as its smooth-rule
+    // position, blending by the mean sharpness of its creased edges when that is below one.
+    fn vertex_point(&self, mesh: &Mesh, v: usize, smooth: Point3) -> Point3 {
+        let sharp_edges: Vec<usize> = self.vertex_edges[v]
+            .iter()
+            .cloned()
+            .filter(|&e| self.edges[e].sharpness > 0.0)
+            .collect();
+
+        if sharp_edges.len() < 2 {
+            return smooth;
+        }
+
+        let position = mesh.positions[v];
+        let sharp = if sharp_edges.len() == 2 {
+            let a = mesh.positions[self.other_vertex(sharp_edges[0], v)];
+            let b = mesh.positions[self.other_vertex(sharp_edges[1], v)];
+            (a + position * 6.0 + b) / 8.0
+        } else {
+            position
+        };
+
+        let sharpness = sharp_edges.iter().map(|&e| self.edges[e].sharpness).sum::<f32>()
+            / sharp_edges.len() as f32;
+        if sharpness >= 1.0 {
+            sharp
+        } else {
+            smooth * (1.0 - sharpness) + sharp * sharpness
+        }
+    }
+
+    fn edge_point(&self, mesh: &Mesh, e: usize, smooth: impl Fn() -> Point3) -> Point3 {
+        let edge = &self.edges[e];
+        let midpoint = (mesh.positions[edge.vertices.0] + mesh.positions[edge.vertices.1]) * 0.5;
+
+        if edge.sharpness >= 1.0 {
+            midpoint
+        } else if edge.sharpness > 0.0 {
+            smooth() * (1.0 - edge.sharpness) + midpoint * edge.sharpness
+        } else {
+            smooth()
+        }
+    }
+
+    // Each creased edge splits into two child edges, one unit less sharp
+    fn child_creases(&self, edge_vertex: impl Fn(usize) -> usize) -> Vec<(usize, usize, f32)> {
+        let mut creases = Vec::new();
+        for (e, edge) in self.edges.iter().enumerate() {
+            if edge.faces.len() == 2 && edge.sharpness > 1.0 {
+                let (a, b) = edge.vertices;
+                creases.push((a, edge_vertex(e), edge.sharpness - 1.0));
+                creases.push((edge_vertex(e), b, edge.sharpness - 1.0));
+            }
+        }
+
+        creases
+    }
+}
+
+fn catmull_clark_step(mesh: &Mesh) -> Mesh {
+    let topology = Topology::new(mesh);
+    let vertex_count = mesh.positions.len();
+    let edge_count = topology.edges.len();
+
+    let face_points: Vec<Point3> =
+        mesh.faces.iter().map(|face| average(face.iter().map(|&v| mesh.positions[v]))).collect();
+
+    let edge_points: Vec<Point3> = (0..edge_count)
+        .map(|e| {
+            topology.edge_point(mesh, e, || {
+                let edge = &topology.edges[e];
+                let (a, b) = edge.vertices;
+                (mesh.positions[a]
+                    + mesh.positions[b]
+                    + face_points[edge.faces[0]]
+                    + face_points[edge.faces[1]])
+                    / 4.0
+            })
+        })
+        .collect();
+
+    let vertex_points: Vec<Point3> = (0..vertex_count)
+        .map(|v| {
+            let valence = topology.vertex_edges[v].len();
+            if valence == 0 {
+                return mesh.positions[v];
+            }
+
+            let n = valence as f32;
+            let q = average(topology.vertex_faces[v].iter().map(|&f| face_points[f]));
+            let r = average(topology.vertex_edges[v].iter().map(|&e| {
+                let (a, b) = topology.edges[e].vertices;
+                (mesh.positions[a] + mesh.positions[b]) * 0.5
+            }));
+            let smooth = (q + r * 2.0 + mesh.positions[v] * (n - 3.0)) / n;
+
+            topology.vertex_point(mesh, v, smooth)
+        })
+        .collect();
+
+    // New vertices are ordered as old vertices, then edge points, then face points
+    let edge_vertex = |e: usize| vertex_count + e;
+    let face_vertex = |f: usize| vertex_count + edge_count + f;
+
+    let mut faces = Vec::new();
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let n = face.len();
+        for i in 0..n {
+            let next = topology.edge(face[i], face[(i + 1) % n]);
+            let previous = topology.edge(face[(i + n - 1) % n], face[i]);
+            faces.push(vec![face[i], edge_vertex(next), face_vertex(f), edge_vertex(previous)]);
+        }
+    }
+
+    let uvs = mesh.uvs.as_ref().map(|uvs| {
+        let mut refined = uvs.clone();
+        refined.extend(
+            topology
+                .edges
+                .iter()
+                .map(|edge| average_uv(&[uvs[edge.vertices.0], uvs[edge.vertices.1]])),
+        );
+        refined.extend(
+            mesh.faces
+                .iter()
+                .map(|face| average_uv(&face.iter().map(|&v| uvs[v]).collect::<Vec<_>>())),
+        );
+        refined
+    });
+
+    let mut positions = vertex_points;
+    positions.extend(edge_points);
+    positions.extend(face_points);
+
+    Mesh { positions, uvs, faces, creases: topology.child_creases(edge_vertex) }
+}
+
+fn loop_step(mesh: &Mesh) -> Mesh {
+    let topology = Topology::new(mesh);
+    let vertex_count = mesh.positions.len();
+
+    let edge_points: Vec<Point3> = (0..topology.edges.len())
+        .map(|e| {
+            topology.edge_point(mesh, e, || {
+                let edge = &topology.edges[e];
+                let (a, b) = edge.vertices;
+                let opposite = |f: usize| {
+                    let v = *mesh.faces[f].iter().find(|&&v| v != a && v != b).unwrap();
+                    mesh.positions[v]
+                };
+
+                (mesh.positions[a] + mesh.positions[b]) * 0.375
+                    + (opposite(edge.faces[0]) + opposite(edge.faces[1])) * 0.125
+            })
+        })
+        .collect();
+
+    let vertex_points: Vec<Point3> = (0..vertex_count)
+        .map(|v| {
+            let valence = topology.vertex_edges[v].len();
+            if valence == 0 {
+                return mesh.positions[v];
+            }
+
+            let n = valence as f32;
+            let w = 0.375 + 0.25 * (2.0 * std::f32::consts::PI / n).cos();
+            let beta = (0.625 - w * w) / n;
+            let neighbors = topology.vertex_edges[v]
+                .iter()
+                .fold(Vec3::default(), |sum, &e| sum + mesh.positions[topology.other_vertex(e, v)]);
+            let smooth = mesh.positions[v] * (1.0 - n * beta) + neighbors * beta;
+
+            topology.vertex_point(mesh, v, smooth)
+        })
+        .collect();
+
+    let edge_vertex = |e: usize| vertex_count + e;
+
+    let mut faces = Vec::new();
+    for face in mesh.faces.iter() {
+        let (a, b, c) = (face[0], face[1], face[2]);
+        let ab = edge_vertex(topology.edge(a, b));
+        let bc = edge_vertex(topology.edge(b, c));
+        let ca = edge_vertex(topology.edge(c, a));
+
+        faces.push(vec![a, ab, ca]);
+        faces.push(vec![b, bc, ab]);
+        faces.push(vec![c, ca, bc]);
+        faces.push(vec![ab, bc, ca]);
+    }
+
+    let uvs = mesh.uvs.as_ref().map(|uvs| {
+        let mut refined = uvs.clone();
+        refined.extend(
+            topology
+                .edges
+                .iter()
+                .map(|edge| average_uv(&[uvs[edge.vertices.0], uvs[edge.vertices.1]])),
+        );
+        refined
+    });
+
+    let mut positions = vertex_points;
+    positions.extend(edge_points);
+
+    Mesh { positions, uvs, faces, creases: topology.child_creases(edge_vertex) }
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn average(points: impl Iterator<Item = Point3>) -> Point3 {
+    let (sum, count) = points.fold((Vec3::default(), 0), |(sum, count), p| (sum + p, count + 1));
+    sum / count as f32
+}
+
+fn average_uv(uvs: &[(f32, f32)]) -> (f32, f32) {
+    let n = uvs.len() as f32;
+    let (u, v) = uvs.iter().fold((0.0, 0.0), |(u, v), uv| (u + uv.0, v + uv.1));
+    (u / n, v / n)
+}
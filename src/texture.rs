@@ -1,4 +1,4 @@
-use image::{ImageBuffer, Pixel, Rgb};
+use image::{ImageBuffer, Pixel, Rgb, Rgba};
 
 use crate::{
     perlin::Perlin,
@@ -16,6 +16,29 @@ pub enum SurfaceTexture<'a> {
     Checkered(Color, Color), // only raw colors to make implementation simpler
     Noise(Perlin, f32),
     Image { buffer: &'a ImageBuffer<Rgb<u8>, Vec<u8>>, width: u32, height: u32 },
+    ImageAlpha { buffer: &'a ImageBuffer<Rgba<u8>, Vec<u8>>, width: u32, height: u32 },
+}
+
+impl SurfaceTexture<'_> {
+    // Solid gray, for parameters that are read as a single scalar
+    pub fn constant(value: f32) -> Self {
+        Self::Solid(Color::new(value, value, value))
+    }
+
+    // Opacity in [0, 1]: the alpha channel of an RGBA image, or the mean of the color channels
+    // for any other texture
+    pub fn alpha(&self, u: f32, v: f32, p: &Point3) -> f32 {
+        match self {
+            Self::ImageAlpha { buffer, width, height } => {
+                let (i, j) = texel(u, v, *width, *height);
+                buffer.get_pixel(i, j).channels()[3] as f32 / 255.0
+            }
+            _ => {
+                let value = self.value(u, v, p);
+                clamp((value.x() + value.y() + value.z()) / 3.0, 0.0, 1.0)
+            }
+        }
+    }
 }
 
 impl<'a> Texture for SurfaceTexture<'a> {
@@ -37,32 +60,50 @@ impl<'a> Texture for SurfaceTexture<'a> {
                     * (1.0 + (scale * p.z() + 10.0 * noise.turb(p)).sin())
             }
             Self::Image { buffer, width, height } => {
-                // Clamp input texture coordinates to [0, 1] x [1, 0]
-                let u = clamp(u, 0.0, 1.0);
-                let v = 1.0 - clamp(v, 0.0, 1.0);
-
-                let mut i = (u * *width as f32) as i32;
-                let mut j = (v * *height as f32) as i32;
-
-                // Clamp integer mapping, since actual integer coordinates should be <= 1.0
-                if i >= (*width as i32) {
-                    i = (width - 1) as i32;
-                }
-
-                if j >= (*height as i32) {
-                    j = (height - 1) as i32;
-                }
-
+                let (i, j) = texel(u, v, *width, *height);
                 let color_scale = 1.0 / 255.0;
-                let pixel = buffer.get_pixel(i as u32, j as u32);
+                let pixel = buffer.get_pixel(i, j);
                 let rgb = pixel.channels();
 
                 Vec3::new(
                     color_scale * rgb[0] as f32,
                     color_scale * rgb[1] as f32,
-                    color_scale * rgb[1] as f32,
+                    color_scale * rgb[2] as f32,
+                )
+            }
+            Self::ImageAlpha { buffer, width, height } => {
+                let (i, j) = texel(u, v, *width, *height);
+
+                let color_scale = 1.0 / 255.0;
+                let rgba = buffer.get_pixel(i, j).channels();
+
+                Vec3::new(
+                    color_scale * rgba[0] as f32,
+                    color_scale * rgba[1] as f32,
+                    color_scale * rgba[2] as f32,
                 )
             }
         }
     }
 }
+
+// Pixel under texture coordinates (u, v), with v running up the image
+fn texel(u: f32, v: f32, width: u32, height: u32) -> (u32, u32) {
+    // Clamp input texture coordinates to [0, 1] x [1, 0]
+    let u = clamp(u, 0.0, 1.0);
+    let v = 1.0 - clamp(v, 0.0, 1.0);
+
+    let mut i = (u * width as f32) as i32;
+    let mut j = (v * height as f32) as i32;
+
+    // Clamp integer mapping, since actual integer coordinates should be <= 1.0
+    if i >= (width as i32) {
+        i = (width - 1) as i32;
+    }
+
+    if j >= (height as i32) {
+        j = (height - 1) as i32;
+    }
+
+    (i as u32, j as u32)
+}
@@ -0,0 +1,185 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::{
+    color,
+    hittable::HitRecord,
+    texture::{SurfaceTexture, Texture},
+    utility::{clamp, PI},
+    vec3::{self, Color},
+};
+
+// Thin transparent layer over a surface, such as an oil slick or anti-reflective coating. Light
+// reflected off its two sides interferes, tinting the reflection by wavelength. Thickness is in
+// nanometres, scaled by the mean of the texture at the hit point.
+#[derive(Copy, Clone)]
+pub struct ThinFilm<'a> {
+    pub thickness: f32,
+    pub thickness_texture: SurfaceTexture<'a>,
+    pub refraction_index: f32,
+}
+
+impl<'a> ThinFilm<'a> {
+    pub fn new(thickness: f32, refraction_index: f32) -> Self {
+        ThinFilm { thickness, thickness_texture: SurfaceTexture::constant(1.0), refraction_index }
+    }
+
+    pub fn thickness_texture(mut self, texture: SurfaceTexture<'a>) -> Self {
+        self.thickness_texture = texture;
+        self
+    }
+
+    fn thickness_at(&self, rec: &HitRecord) -> f32 {
+        let scale = self.thickness_texture.value(rec.u, rec.v, &rec.p);
+        self.thickness * (scale.x() + scale.y() + scale.z()) / 3.0
+    }
+
+    // Reflectance of the film between a medium of index `outside` and a dielectric of index
+    // `inside`, for light arriving from outside at `cos_i` to the normal
+    pub fn dielectric_reflectance(
+        &self,
+        rec: &HitRecord,
+        cos_i: f32,
+        outside: f32,
+        inside: f32,
+    ) -> Color {
+        let thickness = self.thickness_at(rec);
+        let substrate = Complex::new(inside, 0.0);
+
+        color::reflectance_to_rgb(|lambda| {
+            airy(cos_i, outside, self.refraction_index, substrate, thickness, lambda)
+        })
+    }
+
+    // Reflectance of the film over a conductor with per-channel index `eta + ik`, for light
+    // arriving from air. The substrate takes the index of the channel nearest each wavelength.
+    pub fn conductor_reflectance(
+        &self,
+        rec: &HitRecord,
+        cos_i: f32,
+        eta: &Color,
+        k: &Color,
+    ) -> Color {
+        let thickness = self.thickness_at(rec);
+        let (eta, k) = (vec3::unpack(eta), vec3::unpack(k));
+
+        color::reflectance_to_rgb(|lambda| {
+            let channel = if lambda >= 580.0 {
+                0
+            } else if lambda >= 490.0 {
+                1
+            } else {
+                2
+            };
+            let substrate = Complex::new(eta[channel], k[channel]);
+            airy(cos_i, 1.0, self.refraction_index, substrate, thickness, lambda)
+        })
+    }
+}
+
+// Reflectance of a film of index `film` and given thickness between an incident medium and a
+// substrate, summing the multiply reflected waves (Airy), averaged over both polarizations
+fn airy(
+    cos_i: f32,
+    incident: f32,
+    film: f32,
+    substrate: Complex,
+    thickness: f32,
+    lambda: f32,
+) -> f32 {
+    let cos_i = clamp(cos_i, 0.0, 1.0);
+    let sin2_i = 1.0 - cos_i * cos_i;
+
+    // Totally reflected before reaching the film
+    let cos2_f = 1.0 - (incident / film).powi(2) * sin2_i;
+    if cos2_f <= 0.0 {
+        return 1.0;
+    }
+    let cos_f = cos2_f.sqrt();
+
+    let sin_t = Complex::new(incident * sin2_i.sqrt(), 0.0) / substrate;
+    let cos_t = (Complex::new(1.0, 0.0) - sin_t * sin_t).sqrt();
+
+    // Round-trip phase through the film
+    let delta = 4.0 * PI * film * thickness * cos_f / lambda;
+    let phase = Complex::new(delta.cos(), delta.sin());
+
+    let combine = |r12: Complex, r23: Complex| {
+        let r23 = r23 * phase;
+        ((r12 + r23) / (Complex::new(1.0, 0.0) + r12 * r23)).norm_squared()
+    };
+
+    let (ni, nf) = (Complex::new(incident, 0.0), Complex::new(film, 0.0));
+    let (ci, cf) = (Complex::new(cos_i, 0.0), Complex::new(cos_f, 0.0));
+
+    let r12_s = (ni * ci - nf * cf) / (ni * ci + nf * cf);
+    let r23_s = (nf * cf - substrate * cos_t) / (nf * cf + substrate * cos_t);
+    let r12_p = (nf * ci - ni * cf) / (nf * ci + ni * cf);
+    let r23_p = (substrate * cf - nf * cos_t) / (substrate * cf + nf * cos_t);
+
+    (0.5 * (combine(r12_s, r23_s) + combine(r12_p, r23_p))).min(1.0)
+}
+
+// Just enough complex arithmetic for the interface coefficients of absorbing media
+#[derive(Copy, Clone)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Self {
+        Complex { re, im }
+    }
+
+    fn norm_squared(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root
+    fn sqrt(&self) -> Self {
+        let norm = self.norm_squared().sqrt();
+        let re = (0.5 * (norm + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (norm - self.re)).max(0.0).sqrt();
+
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let denominator = other.norm_squared();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+}
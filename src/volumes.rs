@@ -1,26 +1,135 @@
 use crate::{
     aabb::AABB,
+    color,
+    grid::{MajorantGrid, VoxelGrid},
     hittable::{HitModel, HitRecord, Hittable},
     material::Surface,
+    microfacet::fresnel_dielectric,
+    perlin::Perlin,
+    phase::PhaseFunction,
     ray::Ray,
-    texture::SurfaceTexture,
-    utility::{random_double, INFINITY},
-    vec3::{Color, Vec3},
+    texture::{SurfaceTexture, Texture},
+    utility::{random_double, random_int_range, random_unit_sphere, INFINITY},
+    vec3::{self, Color, Point3, Vec3},
 };
 
+// Homogeneous scattering medium filling a `Dielectric`, so it follows the interface and is
+// tracked with the dielectrics a ray is inside instead of being a separate hittable
+#[derive(Copy, Clone)]
+pub struct Interior {
+    pub density: f32,
+    pub albedo: Color,
+    pub phase: PhaseFunction,
+}
+
+impl Interior {
+    pub fn new(density: f32, albedo: Color) -> Self {
+        Interior { density, albedo, phase: PhaseFunction::Isotropic }
+    }
+
+    pub fn phase(mut self, phase: PhaseFunction) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    // Parameter along `r` of a scattering event before `tmax`, if one happens
+    pub fn sample(&self, r: &Ray, tmax: f32) -> Option<f32> {
+        let distance = -(1.0 - random_double(&mut rand::thread_rng())).ln() / self.density;
+        let t = distance / r.direction().length();
+
+        if t < tmax {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    pub fn phase_function(&self) -> Surface<'static> {
+        Surface::Medium(SurfaceTexture::Solid(self.albedo), self.phase)
+    }
+}
+
+// Light given off inside a medium, as the radiance it shows where optically thick: every
+// collision adds it, so thin wisps glow faintly and dense cores at full strength
+#[derive(Clone)]
+pub enum VolumeEmission<'a> {
+    Constant(Color),
+    Texture(Box<SurfaceTexture<'a>>), // evaluated at the collision point
+    Blackbody { temperature: VoxelGrid, hottest: f32, colors: Vec<Color> }, // see `blackbody`
+}
+
+// Temperatures tabulated for `VolumeEmission::Blackbody`, from zero to the grid's hottest voxel
+const BLACKBODY_STEPS: usize = 256;
+
+impl<'a> VolumeEmission<'a> {
+    // Blackbody glow from a grid of temperatures in Kelvin, of luminance `strength` at the hottest
+    // voxel. Cooler voxels are dimmer by how much less visible light a blackbody gives off there.
+    pub fn blackbody(temperature: VoxelGrid, strength: f32) -> Self {
+        let hottest = temperature.max_value();
+        let colors = (0..=BLACKBODY_STEPS)
+            .map(|step| {
+                let kelvin = hottest * step as f32 / BLACKBODY_STEPS as f32;
+                if kelvin > 0.0 {
+                    let luminance = color::blackbody_relative_luminance(kelvin, hottest);
+                    color::blackbody(kelvin, strength * luminance)
+                } else {
+                    Color::new(0.0, 0.0, 0.0)
+                }
+            })
+            .collect();
+
+        Self::Blackbody { temperature, hottest, colors }
+    }
+
+    pub fn radiance(&self, p: &Point3) -> Color {
+        match self {
+            Self::Constant(color) => *color,
+            Self::Texture(texture) => texture.value(0.0, 0.0, p),
+            Self::Blackbody { temperature, hottest, colors } => {
+                let kelvin = temperature.value(p);
+                if kelvin <= 0.0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+
+                // Interpolate the table, whose last entry is the hottest voxel
+                let last = (colors.len() - 1) as f32;
+                let position = (kelvin / hottest * last).min(last);
+                let index = (position as usize).min(colors.len() - 2);
+                let fraction = position - index as f32;
+                colors[index] * (1.0 - fraction) + colors[index + 1] * fraction
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Constant<'a> {
     neg_inv_density: f32,
     boundary: Box<HitModel<'a>>,
     phase_function: Surface<'a>,
+    emission: Option<VolumeEmission<'a>>,
 }
 
 impl<'a> Constant<'a> {
-    pub fn new(density: f32, boundary: HitModel<'a>, phase_function: Color) -> Self {
+    pub fn new(density: f32, boundary: HitModel<'a>, albedo: Color) -> Self {
+        Constant::with_phase(density, boundary, albedo, PhaseFunction::Isotropic)
+    }
+
+    pub fn with_phase(
+        density: f32,
+        boundary: HitModel<'a>,
+        albedo: Color,
+        phase: PhaseFunction,
+    ) -> Self {
         let neg_inv_density = -1.0 / density;
         let boundary = Box::new(boundary);
-        let phase_function = Surface::Isotropic(SurfaceTexture::Solid(phase_function));
-        Constant { neg_inv_density, boundary, phase_function }
+        let phase_function = Surface::Medium(SurfaceTexture::Solid(albedo), phase);
+        Constant { neg_inv_density, boundary, phase_function, emission: None }
+    }
+
+    pub fn emission(mut self, emission: VolumeEmission<'a>) -> Self {
+        self.emission = Some(emission);
+        self
     }
 }
 
@@ -59,7 +168,12 @@ impl<'a> Hittable for Constant<'a> {
                 let front_face = true;
                 let material = &self.phase_function;
 
-                Some(HitRecord::new(p, normal, t, 0.0, 0.0, front_face, material))
+                let mut rec = HitRecord::new(p, normal, t, 0.0, 0.0, front_face, material);
+                if let Some(emission) = &self.emission {
+                    rec.emission = emission.radiance(&p);
+                }
+
+                Some(rec)
             } else {
                 None
             }
@@ -72,3 +186,197 @@ impl<'a> Hittable for Constant<'a> {
         self.boundary.bounding_box(t0, t1)
     }
 }
+
+// Random-walk subsurface scattering inside the closed object it's the surface of, behind a
+// smooth dielectric interface. Light refracted in takes exponentially distributed steps between
+// isotropic scattering events until it refracts back out. Mean free path and single-scattering
+// albedo are per color channel.
+#[derive(Copy, Clone)]
+pub struct Subsurface {
+    albedo: Color,
+    extinction: [f32; 3], // per unit distance, the inverse of the mean free path
+    refraction_index: f32,
+}
+
+// Walks this long have lost all but a negligible part of their energy
+const MAX_WALK_STEPS: usize = 256;
+
+impl Subsurface {
+    pub fn new(albedo: Color, mean_free_path: Color, refraction_index: f32) -> Self {
+        let extinction = vec3::unpack(&mean_free_path).map(|distance| 1.0 / distance);
+        Subsurface { albedo, extinction, refraction_index }
+    }
+
+    // Carries a ray hitting the outside of the object in `rec` through the medium, finding where
+    // it leaves against that same object, instanced as it was hit. Returns the ray leaving the
+    // surface, reflected or after a walk through the interior, and its throughput; `None` if the
+    // walk was absorbed.
+    pub fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let mut rng = rand::thread_rng();
+        let direction = vec3::unit_vector(ray.direction());
+
+        // Rays starting inside only come from a camera placed there, which isn't supported
+        let object = rec.object?;
+        if !rec.front_face {
+            return None;
+        }
+
+        let cos_theta = (-direction).dot(&rec.normal).min(1.0);
+        let ratio = 1.0 / self.refraction_index;
+        if random_double(&mut rng) < fresnel_dielectric(cos_theta, ratio) {
+            return Some((
+                Ray::new(rec.p, vec3::reflect(&direction, &rec.normal), ray.time()),
+                Color::new(1.0, 1.0, 1.0),
+            ));
+        }
+
+        let mut p = rec.p;
+        let mut direction = vec3::unit_vector(&vec3::refract(&direction, &rec.normal, ratio));
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+
+        for _ in 0..MAX_WALK_STEPS {
+            let exit = object.hit(&Ray::new(p, direction, ray.time()), 0.0001, INFINITY)?;
+
+            // Sample a distance with one channel's extinction, weighting by the average density
+            // over all three so every channel stays unbiased
+            let channel = random_int_range(&mut rng, 0, 2) as usize;
+            let distance = -(1.0 - random_double(&mut rng)).ln() / self.extinction[channel];
+            let t = distance.min(exit.t);
+            let transmittance = self.extinction.map(|sigma| (-sigma * t).exp());
+
+            if distance < exit.t {
+                let density = [0, 1, 2].map(|c| self.extinction[c] * transmittance[c]);
+                let pdf = (density[0] + density[1] + density[2]) / 3.0;
+                throughput =
+                    throughput * self.albedo * Color::new(density[0], density[1], density[2]) / pdf;
+
+                p += direction * distance;
+                direction = vec3::unit_vector(&random_unit_sphere(&mut rng));
+                continue;
+            }
+
+            let probability = (transmittance[0] + transmittance[1] + transmittance[2]) / 3.0;
+            throughput = throughput
+                * Color::new(transmittance[0], transmittance[1], transmittance[2])
+                / probability;
+
+            // At the boundary from inside: reflect back in, or refract out and end the walk
+            let cos_theta = (-direction).dot(&exit.normal).min(1.0);
+            if random_double(&mut rng) < fresnel_dielectric(cos_theta, self.refraction_index) {
+                p = exit.p;
+                direction = vec3::reflect(&direction, &exit.normal);
+                continue;
+            }
+
+            let refracted = vec3::refract(&direction, &exit.normal, self.refraction_index);
+            return Some((Ray::new(exit.p, refracted, ray.time()), throughput));
+        }
+
+        None
+    }
+}
+
+// Where a `Heterogeneous` medium's density comes from: a voxel grid, or turbulence scaled by
+// `frequency` in space and clamped to one, times `scale`
+#[derive(Clone)]
+pub enum DensityField {
+    Grid(VoxelGrid),
+    Noise { perlin: Box<Perlin>, frequency: f32, scale: f32 },
+}
+
+impl DensityField {
+    pub fn noise(frequency: f32, scale: f32) -> Self {
+        DensityField::Noise { perlin: Box::new(Perlin::new()), frequency, scale }
+    }
+
+    pub fn density(&self, p: &Point3) -> f32 {
+        match self {
+            Self::Grid(grid) => grid.value(p),
+            Self::Noise { perlin, frequency, scale } => {
+                scale * perlin.turb(&(*p * *frequency)).min(1.0)
+            }
+        }
+    }
+
+    // Upper bounds on the density over the field, within `bounds`
+    pub fn majorant(&self, bounds: &AABB) -> MajorantGrid {
+        match self {
+            Self::Grid(grid) => MajorantGrid::from_voxels(grid, MAJORANT_BLOCK),
+            Self::Noise { scale, .. } => MajorantGrid::constant(bounds, *scale),
+        }
+    }
+}
+
+// Voxels a side covered by each cell of a grid's majorant
+const MAJORANT_BLOCK: usize = 8;
+
+// Participating medium of varying density inside a boundary, sampled by delta tracking: steps
+// are taken through a fictitious medium at the majorant density, and each tentative collision
+// is a real one with probability of the local density over the majorant. Free of bias however
+// the density varies, as long as the majorant bounds it. Place it in the world with the
+// `Translate` and `RotateY` instances.
+#[derive(Clone)]
+pub struct Heterogeneous<'a> {
+    boundary: Box<HitModel<'a>>,
+    density: DensityField,
+    majorant: MajorantGrid,
+    albedo: Color,
+    phase_function: Surface<'a>,
+    emission: Option<VolumeEmission<'a>>,
+}
+
+impl<'a> Heterogeneous<'a> {
+    pub fn new(boundary: HitModel<'a>, density: DensityField, albedo: Color) -> Self {
+        let bounds =
+            boundary.bounding_box(0.0, 1.0).expect("Heterogeneous media need a bounded boundary");
+        let majorant = density.majorant(&bounds);
+        Heterogeneous {
+            boundary: Box::new(boundary),
+            density,
+            majorant,
+            albedo,
+            phase_function: Surface::Medium(
+                SurfaceTexture::Solid(albedo),
+                PhaseFunction::Isotropic,
+            ),
+            emission: None,
+        }
+    }
+
+    pub fn phase(mut self, phase: PhaseFunction) -> Self {
+        self.phase_function = Surface::Medium(SurfaceTexture::Solid(self.albedo), phase);
+        self
+    }
+
+    pub fn emission(mut self, emission: VolumeEmission<'a>) -> Self {
+        self.emission = Some(emission);
+        self
+    }
+}
+
+impl<'a> Hittable for Heterogeneous<'a> {
+    fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let rec1 = self.boundary.hit(r, -INFINITY, INFINITY)?;
+        let rec2 = self.boundary.hit(r, rec1.t + 0.0001, INFINITY)?;
+
+        let start = rec1.t.max(tmin).max(0.0);
+        let end = rec2.t.min(tmax);
+        if start >= end {
+            return None;
+        }
+
+        let t = self.majorant.delta_track(r, start, end, |p| self.density.density(p))?;
+        let p = r.at(t);
+        let normal = Vec3::new(1.0, 0.0, 0.0);
+        let mut rec = HitRecord::new(p, normal, t, 0.0, 0.0, true, &self.phase_function);
+        if let Some(emission) = &self.emission {
+            rec.emission = emission.radiance(&p);
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.boundary.bounding_box(t0, t1)
+    }
+}